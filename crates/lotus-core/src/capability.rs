@@ -2,6 +2,30 @@
 
 use crate::entity::EntityId;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Error reading a capability's params.
+#[derive(Debug, Error, PartialEq)]
+pub enum CapabilityError {
+    #[error("capability {cap_type} is missing param '{key}'")]
+    MissingParam { cap_type: String, key: String },
+
+    #[error("capability {cap_type} param '{key}' must be {expected}")]
+    WrongParamType {
+        cap_type: String,
+        key: String,
+        expected: &'static str,
+    },
+
+    #[error("capability {cap_type} expired at {expires_at}")]
+    Expired { cap_type: String, expires_at: i64 },
+
+    #[error("capability {cap_type} does not allow operation '{op}'")]
+    OperationNotAllowed { cap_type: String, op: String },
+
+    #[error("delegated capability {cap_type} cannot widen param '{key}'")]
+    NotNarrower { cap_type: String, key: String },
+}
 
 /// A capability token granting specific permissions.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,6 +38,9 @@ pub struct Capability {
     pub cap_type: String,
     /// Parameters for the capability (e.g., {"target_id": 42}).
     pub params: serde_json::Value,
+    /// Capability this one was delegated from, if any.
+    #[serde(default)]
+    pub parent_id: Option<String>,
 }
 
 impl Capability {
@@ -37,6 +64,137 @@ impl Capability {
             _ => self.params == *required_params,
         }
     }
+
+    /// Get a required string param.
+    pub fn param_str(&self, key: &str) -> Result<&str, CapabilityError> {
+        self.param(key)?
+            .as_str()
+            .ok_or_else(|| self.wrong_type(key, "a string"))
+    }
+
+    /// Get a required integer param.
+    pub fn param_i64(&self, key: &str) -> Result<i64, CapabilityError> {
+        self.param(key)?
+            .as_i64()
+            .ok_or_else(|| self.wrong_type(key, "an integer"))
+    }
+
+    /// Get a required boolean param.
+    pub fn param_bool(&self, key: &str) -> Result<bool, CapabilityError> {
+        self.param(key)?
+            .as_bool()
+            .ok_or_else(|| self.wrong_type(key, "a boolean"))
+    }
+
+    /// Check that this capability may be used for `op` at time `now_ms`.
+    ///
+    /// Enforces two optional params shared by all capability types:
+    /// - `expires_at`: ms since Unix epoch after which the capability is dead
+    /// - `ops`: list of allowed operations (e.g. `["read", "mkdir"]`)
+    ///
+    /// A missing param imposes no restriction. Plugins call this before
+    /// acting, alongside their own type-specific checks.
+    pub fn check_use(&self, op: &str, now_ms: i64) -> Result<(), CapabilityError> {
+        if let Some(expires_at) = self.params.get("expires_at") {
+            let expires_at = expires_at
+                .as_i64()
+                .ok_or_else(|| self.wrong_type("expires_at", "an integer"))?;
+            if now_ms >= expires_at {
+                return Err(CapabilityError::Expired {
+                    cap_type: self.cap_type.clone(),
+                    expires_at,
+                });
+            }
+        }
+
+        if let Some(ops) = self.params.get("ops") {
+            let ops = ops
+                .as_array()
+                .ok_or_else(|| self.wrong_type("ops", "a list of strings"))?;
+            let mut allowed = false;
+            for allowed_op in ops {
+                let allowed_op = allowed_op
+                    .as_str()
+                    .ok_or_else(|| self.wrong_type("ops", "a list of strings"))?;
+                allowed |= allowed_op == op;
+            }
+            if !allowed {
+                return Err(CapabilityError::OperationNotAllowed {
+                    cap_type: self.cap_type.clone(),
+                    op: op.to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compute the params for a capability delegated from this one.
+    ///
+    /// `restrictions` is an object merged over this capability's params. It
+    /// may only narrow them: `expires_at` can move earlier, `ops` can drop
+    /// operations, and any other key must keep its current value.
+    pub fn narrow(
+        &self,
+        restrictions: &serde_json::Value,
+    ) -> Result<serde_json::Value, CapabilityError> {
+        let Some(restrictions) = restrictions.as_object() else {
+            return Err(self.wrong_type("restrictions", "an object"));
+        };
+        let mut params = self.params.clone();
+        if !params.is_object() {
+            params = serde_json::Value::Object(serde_json::Map::new());
+        }
+
+        for (key, value) in restrictions {
+            let current = self.params.get(key);
+            let narrower = match key.as_str() {
+                "expires_at" => {
+                    let expires_at = value
+                        .as_i64()
+                        .ok_or_else(|| self.wrong_type(key, "an integer"))?;
+                    current.is_none_or(|c| c.as_i64().is_some_and(|c| expires_at <= c))
+                }
+                "ops" => {
+                    let ops = value
+                        .as_array()
+                        .filter(|ops| ops.iter().all(|op| op.is_string()))
+                        .ok_or_else(|| self.wrong_type(key, "a list of strings"))?;
+                    current.is_none_or(|c| {
+                        c.as_array()
+                            .is_some_and(|allowed| ops.iter().all(|op| allowed.contains(op)))
+                    })
+                }
+                _ => current == Some(value),
+            };
+            if !narrower {
+                return Err(CapabilityError::NotNarrower {
+                    cap_type: self.cap_type.clone(),
+                    key: key.clone(),
+                });
+            }
+            params[key] = value.clone();
+        }
+
+        Ok(params)
+    }
+
+    fn param(&self, key: &str) -> Result<&serde_json::Value, CapabilityError> {
+        self.params
+            .get(key)
+            .ok_or_else(|| CapabilityError::MissingParam {
+                cap_type: self.cap_type.clone(),
+                key: key.to_string(),
+            })
+    }
+
+    fn wrong_type(&self, key: &str, expected: &'static str) -> CapabilityError {
+        CapabilityError::WrongParamType {
+            cap_type: self.cap_type.clone(),
+            key: key.to_string(),
+            expected,
+        }
+    }
 }
 
 /// Common capability types.
@@ -51,6 +209,8 @@ pub mod cap_types {
     pub const NET_REQUEST: &str = "net.request";
     /// Execute arbitrary system commands.
     pub const SYSTEM_EXEC: &str = "system.exec";
+    /// Schedule tasks (optional params: `max_tasks`, `min_interval_ms`).
+    pub const SCHEDULER: &str = "scheduler";
 }
 
 #[cfg(test)]
@@ -65,6 +225,7 @@ mod tests {
             owner_id: 1,
             cap_type: "entity.control".to_string(),
             params: json!({"target_id": 42}),
+            parent_id: None,
         };
 
         assert!(cap.permits("entity.control", &json!({"target_id": 42})));
@@ -79,6 +240,7 @@ mod tests {
             owner_id: 1,
             cap_type: "fs.read".to_string(),
             params: json!({"path": "/home/user", "recursive": true}),
+            parent_id: None,
         };
 
         // Subset of params should match
@@ -86,4 +248,193 @@ mod tests {
         // But extra required params should fail
         assert!(!cap.permits("fs.read", &json!({"path": "/home/user", "execute": true})));
     }
+
+    #[test]
+    fn test_capability_typed_params() {
+        let cap = Capability {
+            id: "test-cap".to_string(),
+            owner_id: 1,
+            cap_type: "fs.read".to_string(),
+            params: json!({"path": "/home/user", "max_bytes": 1024, "recursive": true}),
+            parent_id: None,
+        };
+
+        assert_eq!(cap.param_str("path"), Ok("/home/user"));
+        assert_eq!(cap.param_i64("max_bytes"), Ok(1024));
+        assert_eq!(cap.param_bool("recursive"), Ok(true));
+    }
+
+    #[test]
+    fn test_capability_typed_params_missing() {
+        let cap = Capability {
+            id: "test-cap".to_string(),
+            owner_id: 1,
+            cap_type: "fs.read".to_string(),
+            params: json!({}),
+            parent_id: None,
+        };
+
+        let err = cap.param_str("path").unwrap_err();
+        assert_eq!(
+            err,
+            CapabilityError::MissingParam {
+                cap_type: "fs.read".to_string(),
+                key: "path".to_string(),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "capability fs.read is missing param 'path'"
+        );
+    }
+
+    #[test]
+    fn test_capability_typed_params_wrong_type() {
+        let cap = Capability {
+            id: "test-cap".to_string(),
+            owner_id: 1,
+            cap_type: "fs.read".to_string(),
+            params: json!({"path": 42, "max_bytes": "big", "recursive": "yes"}),
+            parent_id: None,
+        };
+
+        assert_eq!(
+            cap.param_str("path").unwrap_err().to_string(),
+            "capability fs.read param 'path' must be a string"
+        );
+        assert!(matches!(
+            cap.param_i64("max_bytes"),
+            Err(CapabilityError::WrongParamType {
+                expected: "an integer",
+                ..
+            })
+        ));
+        assert!(matches!(
+            cap.param_bool("recursive"),
+            Err(CapabilityError::WrongParamType {
+                expected: "a boolean",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_capability_check_use_unrestricted() {
+        let cap = Capability {
+            id: "test-cap".to_string(),
+            owner_id: 1,
+            cap_type: "fs.read".to_string(),
+            params: json!({"path": "/home/user"}),
+            parent_id: None,
+        };
+
+        assert_eq!(cap.check_use("read", 1_000), Ok(()));
+        assert_eq!(cap.check_use("remove", i64::MAX), Ok(()));
+    }
+
+    #[test]
+    fn test_capability_check_use_expired() {
+        let cap = Capability {
+            id: "test-cap".to_string(),
+            owner_id: 1,
+            cap_type: "fs.read".to_string(),
+            params: json!({"path": "/home/user", "expires_at": 5_000}),
+            parent_id: None,
+        };
+
+        assert_eq!(cap.check_use("read", 4_999), Ok(()));
+        assert_eq!(
+            cap.check_use("read", 5_000),
+            Err(CapabilityError::Expired {
+                cap_type: "fs.read".to_string(),
+                expires_at: 5_000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_capability_check_use_ops() {
+        let cap = Capability {
+            id: "test-cap".to_string(),
+            owner_id: 1,
+            cap_type: "fs.write".to_string(),
+            params: json!({"path": "/home/user", "ops": ["read", "write"]}),
+            parent_id: None,
+        };
+
+        assert_eq!(cap.check_use("write", 0), Ok(()));
+        assert_eq!(
+            cap.check_use("remove", 0).unwrap_err().to_string(),
+            "capability fs.write does not allow operation 'remove'"
+        );
+
+        let malformed = Capability {
+            params: json!({"ops": "read"}),
+            ..cap
+        };
+        assert!(matches!(
+            malformed.check_use("read", 0),
+            Err(CapabilityError::WrongParamType { .. })
+        ));
+    }
+
+    #[test]
+    fn test_capability_narrow() {
+        let cap = Capability {
+            id: "test-cap".to_string(),
+            owner_id: 1,
+            cap_type: "fs.write".to_string(),
+            params: json!({"path": "/home/user", "ops": ["read", "write"], "expires_at": 5_000}),
+            parent_id: None,
+        };
+
+        assert_eq!(
+            cap.narrow(&json!({"ops": ["read"], "expires_at": 1_000}))
+                .unwrap(),
+            json!({"path": "/home/user", "ops": ["read"], "expires_at": 1_000})
+        );
+        // Restating a param unchanged is fine
+        assert_eq!(
+            cap.narrow(&json!({"path": "/home/user"})).unwrap(),
+            cap.params
+        );
+
+        for widening in [
+            json!({"ops": ["read", "remove"]}),
+            json!({"expires_at": 9_000}),
+            json!({"path": "/"}),
+            json!({"recursive": true}),
+        ] {
+            assert!(
+                matches!(
+                    cap.narrow(&widening),
+                    Err(CapabilityError::NotNarrower { .. })
+                ),
+                "{} should be rejected",
+                widening
+            );
+        }
+    }
+
+    #[test]
+    fn test_capability_narrow_unrestricted() {
+        let cap = Capability {
+            id: "test-cap".to_string(),
+            owner_id: 1,
+            cap_type: "fs.read".to_string(),
+            params: json!({"path": "/home/user"}),
+            parent_id: None,
+        };
+
+        // A missing expires_at or ops can be introduced
+        assert_eq!(
+            cap.narrow(&json!({"ops": ["read"], "expires_at": 1_000}))
+                .unwrap(),
+            json!({"path": "/home/user", "ops": ["read"], "expires_at": 1_000})
+        );
+        assert!(matches!(
+            cap.narrow(&json!({"ops": "read"})),
+            Err(CapabilityError::WrongParamType { .. })
+        ));
+    }
 }
@@ -48,3 +48,24 @@ pub struct Verb {
     /// If set, caller must hold a capability of this type to execute the verb.
     pub required_capability: Option<String>,
 }
+
+/// A directed reference from one entity to another (wikilink, owner, etc.).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntityReference {
+    pub source_id: EntityId,
+    pub target_id: EntityId,
+    /// What kind of reference this is (e.g. "link", "owner").
+    pub kind: String,
+}
+
+/// A verb subscribed to a named event emitted by an entity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventSubscription {
+    pub id: i64,
+    /// Entity whose events are watched.
+    pub source_id: EntityId,
+    pub event: String,
+    /// Entity whose verb handles the event.
+    pub handler_id: EntityId,
+    pub handler_verb: String,
+}
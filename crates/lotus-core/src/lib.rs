@@ -5,7 +5,9 @@ pub mod entity;
 pub mod scheduler;
 pub mod storage;
 
-pub use capability::{Capability, cap_types};
-pub use entity::{Entity, EntityId, Verb};
+pub use capability::{Capability, CapabilityError, cap_types};
+pub use entity::{Entity, EntityId, EntityReference, EventSubscription, Verb};
 pub use scheduler::{ScheduledTask, Scheduler, SchedulerError};
-pub use storage::{StorageError, WorldStorage};
+pub use storage::{
+    CapabilitySpec, Checkpoint, EntityPage, StorageError, StorageOptions, WorldStorage,
+};
@@ -3,7 +3,7 @@
 //! The scheduler manages tasks stored in the database and executes them
 //! when their scheduled time arrives. Tasks are persisted to survive restarts.
 
-use crate::{StorageError, WorldStorage};
+use crate::{Capability, CapabilityError, StorageError, WorldStorage, cap_types};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
@@ -17,11 +17,29 @@ pub enum SchedulerError {
 
     #[error("Task execution error: {0}")]
     Execution(String),
+
+    #[error("Task not found: {0}")]
+    TaskNotFound(i64),
+
+    #[error("Entity {0} lacks the scheduler capability")]
+    Unauthorized(i64),
+
+    #[error("Scheduler limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    #[error("Capability error: {0}")]
+    Capability(#[from] CapabilityError),
 }
 
 // Re-export ScheduledTask from storage for convenience
 pub use crate::storage::ScheduledTask;
 
+/// How long a due task waits before it gains one priority level.
+///
+/// Bounds how long low-priority tasks can be pushed back by newer
+/// high-priority ones.
+pub const PRIORITY_AGING_MS: i64 = 10_000;
+
 /// Task scheduler that executes verbs after a delay.
 pub struct Scheduler {
     storage: Arc<Mutex<WorldStorage>>,
@@ -54,20 +72,156 @@ impl Scheduler {
         verb: &str,
         args: serde_json::Value,
         delay_ms: u64,
+    ) -> Result<i64, SchedulerError> {
+        self.schedule_with_priority(entity_id, verb, args, delay_ms, 0)
+            .await
+    }
+
+    /// Schedule a task with a priority.
+    ///
+    /// Among due tasks, higher priorities run first (see [`PRIORITY_AGING_MS`]
+    /// for how waiting tasks catch up). The default priority is 0.
+    pub async fn schedule_with_priority(
+        &self,
+        entity_id: i64,
+        verb: &str,
+        args: serde_json::Value,
+        delay_ms: u64,
+        priority: i64,
     ) -> Result<i64, SchedulerError> {
         let execute_at = (current_time_ms() + delay_ms) as i64;
         let storage = self.storage.lock().await;
         let task_id = storage
-            .schedule_task(entity_id, verb, args, execute_at)
+            .schedule_task_with_priority(entity_id, verb, args, execute_at, priority)
             .await?;
         Ok(task_id)
     }
 
+    /// Schedule a task on behalf of an entity, enforcing its scheduler capability.
+    ///
+    /// This is what script-facing schedule opcodes should use. The entity must
+    /// own a [`cap_types::SCHEDULER`] capability whose limits allow the task:
+    /// - `max_tasks`: most pending tasks the entity may have at once
+    /// - `min_interval_ms`: shortest allowed delay before the task runs
+    ///
+    /// The capability must also pass [`Capability::check_use`] for the
+    /// `"schedule"` op, so expired capabilities and ones whose `ops` leave out
+    /// scheduling are refused. Missing params mean no limit. With several
+    /// scheduler capabilities, the task is allowed if any one of them permits it.
+    pub async fn schedule_checked(
+        &self,
+        entity_id: i64,
+        verb: &str,
+        args: serde_json::Value,
+        delay_ms: u64,
+        priority: i64,
+    ) -> Result<i64, SchedulerError> {
+        let now = current_time_ms() as i64;
+        // Scripts choose the delay, so reject anything that doesn't fit
+        // rather than overflowing into a past execute_at
+        let Some(execute_at) = i64::try_from(delay_ms)
+            .ok()
+            .and_then(|delay| now.checked_add(delay))
+        else {
+            return Err(SchedulerError::LimitExceeded(format!(
+                "delay of {}ms is out of range",
+                delay_ms
+            )));
+        };
+        // Hold the lock across check and insert so concurrent calls can't
+        // both slip under `max_tasks`
+        let storage = self.storage.lock().await;
+
+        let caps: Vec<Capability> = storage
+            .get_capabilities(entity_id)
+            .await?
+            .into_iter()
+            .filter(|cap| cap.cap_type == cap_types::SCHEDULER)
+            .collect();
+        if caps.is_empty() {
+            return Err(SchedulerError::Unauthorized(entity_id));
+        }
+
+        let pending = storage.count_entity_tasks(entity_id).await?;
+        let mut allowed = Ok(());
+        for cap in &caps {
+            allowed = cap
+                .check_use("schedule", now)
+                .map_err(SchedulerError::from)
+                .and_then(|()| check_scheduler_limits(cap, pending, execute_at - now));
+            if allowed.is_ok() {
+                break;
+            }
+        }
+        allowed?;
+
+        let task_id = storage
+            .schedule_task_with_priority(entity_id, verb, args, execute_at, priority)
+            .await?;
+        Ok(task_id)
+    }
+
+    /// Emit a named event from `source_id`, queueing every subscribed handler.
+    ///
+    /// Each handler verb is scheduled to run immediately with positional args
+    /// `[source_id, event, payload]`. Returns the new task ids, in
+    /// subscription order; all are queued or none are.
+    pub async fn emit(
+        &self,
+        source_id: i64,
+        event: &str,
+        payload: serde_json::Value,
+    ) -> Result<Vec<i64>, SchedulerError> {
+        let execute_at = current_time_ms() as i64;
+        let args = serde_json::json!([source_id, event, payload]);
+        let mut storage = self.storage.lock().await;
+        let task_ids = storage
+            .transaction(async |s| {
+                let mut task_ids = Vec::new();
+                for sub in s.get_subscribers(source_id, event).await? {
+                    task_ids.push(
+                        s.schedule_task(
+                            sub.handler_id,
+                            &sub.handler_verb,
+                            args.clone(),
+                            execute_at,
+                        )
+                        .await?,
+                    );
+                }
+                Ok(task_ids)
+            })
+            .await?;
+        Ok(task_ids)
+    }
+
+    /// List pending tasks for an entity, soonest first.
+    ///
+    /// Each task's `execute_at` is its next fire time (ms since Unix epoch).
+    pub async fn list(&self, entity_id: i64) -> Result<Vec<ScheduledTask>, SchedulerError> {
+        let storage = self.storage.lock().await;
+        let tasks = storage.get_entity_tasks(entity_id).await?;
+        Ok(tasks)
+    }
+
+    /// Cancel a pending task owned by `entity_id`.
+    ///
+    /// Tasks belonging to other entities are reported as not found, so callers
+    /// can only manage their own entity's tasks.
+    pub async fn cancel(&self, entity_id: i64, task_id: i64) -> Result<(), SchedulerError> {
+        let storage = self.storage.lock().await;
+        if storage.delete_entity_task(entity_id, task_id).await? {
+            Ok(())
+        } else {
+            Err(SchedulerError::TaskNotFound(task_id))
+        }
+    }
+
     /// Get all tasks that are due for execution.
     async fn get_due_tasks(&self) -> Result<Vec<ScheduledTask>, SchedulerError> {
         let now = current_time_ms() as i64;
         let storage = self.storage.lock().await;
-        let tasks = storage.get_due_tasks(now).await?;
+        let tasks = storage.get_due_tasks(now, PRIORITY_AGING_MS).await?;
         Ok(tasks)
     }
 
@@ -131,6 +285,40 @@ impl Scheduler {
     }
 }
 
+/// Check a task against one scheduler capability's limits.
+fn check_scheduler_limits(
+    cap: &Capability,
+    pending: u64,
+    delay_ms: i64,
+) -> Result<(), SchedulerError> {
+    if let Some(max_tasks) = optional_param_i64(cap, "max_tasks")?
+        && i64::try_from(pending).unwrap_or(i64::MAX) >= max_tasks
+    {
+        return Err(SchedulerError::LimitExceeded(format!(
+            "{} pending tasks, max_tasks is {}",
+            pending, max_tasks
+        )));
+    }
+    if let Some(min_interval) = optional_param_i64(cap, "min_interval_ms")?
+        && delay_ms < min_interval
+    {
+        return Err(SchedulerError::LimitExceeded(format!(
+            "delay of {}ms is below min_interval_ms {}",
+            delay_ms, min_interval
+        )));
+    }
+    Ok(())
+}
+
+/// Read an integer param, treating a missing one as no limit.
+fn optional_param_i64(cap: &Capability, key: &str) -> Result<Option<i64>, SchedulerError> {
+    match cap.param_i64(key) {
+        Ok(value) => Ok(Some(value)),
+        Err(CapabilityError::MissingParam { .. }) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
 /// Get current time in milliseconds since Unix epoch.
 fn current_time_ms() -> u64 {
     SystemTime::now()
@@ -248,4 +436,339 @@ mod tests {
         let tasks = scheduler.get_due_tasks().await.unwrap();
         assert_eq!(tasks.len(), 0, "Future task not yet due");
     }
+
+    #[tokio::test]
+    async fn test_list_and_cancel() {
+        let storage = Arc::new(Mutex::new(WorldStorage::in_memory().await.unwrap()));
+        let scheduler = Scheduler::new(Arc::clone(&storage), 100);
+
+        let (owner_id, other_id) = {
+            let storage = storage.lock().await;
+            let owner = storage
+                .create_entity(serde_json::json!({"name": "Owner"}), None)
+                .await
+                .unwrap();
+            let other = storage
+                .create_entity(serde_json::json!({"name": "Other"}), None)
+                .await
+                .unwrap();
+            (owner, other)
+        };
+
+        let later = scheduler
+            .schedule(owner_id, "later", serde_json::json!([]), 20_000)
+            .await
+            .unwrap();
+        let sooner = scheduler
+            .schedule(owner_id, "sooner", serde_json::json!([]), 10_000)
+            .await
+            .unwrap();
+        scheduler
+            .schedule(other_id, "unrelated", serde_json::json!([]), 10_000)
+            .await
+            .unwrap();
+
+        // Only the owner's tasks, soonest first
+        let tasks = scheduler.list(owner_id).await.unwrap();
+        let ids: Vec<i64> = tasks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![sooner, later]);
+        assert!(tasks[0].execute_at < tasks[1].execute_at);
+
+        // Another entity can't cancel the owner's task
+        assert!(matches!(
+            scheduler.cancel(other_id, sooner).await,
+            Err(SchedulerError::TaskNotFound(id)) if id == sooner
+        ));
+
+        scheduler.cancel(owner_id, sooner).await.unwrap();
+        let tasks = scheduler.list(owner_id).await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, later);
+    }
+
+    #[tokio::test]
+    async fn test_higher_priority_runs_first() {
+        let storage = Arc::new(Mutex::new(WorldStorage::in_memory().await.unwrap()));
+        let scheduler = Scheduler::new(Arc::clone(&storage), 100);
+
+        let entity_id = {
+            let storage = storage.lock().await;
+            storage
+                .create_entity(serde_json::json!({"name": "Test"}), None)
+                .await
+                .unwrap()
+        };
+
+        // Same due time; the low-priority one was scheduled first
+        let execute_at = current_time_ms() as i64 - 1;
+        {
+            let storage = storage.lock().await;
+            storage
+                .schedule_task_with_priority(entity_id, "low", serde_json::json!([]), execute_at, 0)
+                .await
+                .unwrap();
+            storage
+                .schedule_task_with_priority(
+                    entity_id,
+                    "high",
+                    serde_json::json!([]),
+                    execute_at,
+                    5,
+                )
+                .await
+                .unwrap();
+        }
+
+        let mut order = Vec::new();
+        scheduler
+            .process(|task| {
+                order.push(task.verb);
+                async { Ok(()) }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(order, vec!["high", "low"]);
+    }
+
+    #[tokio::test]
+    async fn test_overdue_low_priority_is_not_starved() {
+        let storage = WorldStorage::in_memory().await.unwrap();
+        let entity_id = storage
+            .create_entity(serde_json::json!({"name": "Test"}), None)
+            .await
+            .unwrap();
+
+        let now = current_time_ms() as i64;
+        // Waiting for 3 aging periods outranks a fresh task 2 levels higher
+        storage
+            .schedule_task_with_priority(
+                entity_id,
+                "old_low",
+                serde_json::json!([]),
+                now - 3 * PRIORITY_AGING_MS,
+                0,
+            )
+            .await
+            .unwrap();
+        storage
+            .schedule_task_with_priority(entity_id, "new_high", serde_json::json!([]), now, 2)
+            .await
+            .unwrap();
+
+        let tasks = storage.get_due_tasks(now, PRIORITY_AGING_MS).await.unwrap();
+        let verbs: Vec<&str> = tasks.iter().map(|t| t.verb.as_str()).collect();
+        assert_eq!(verbs, vec!["old_low", "new_high"]);
+    }
+
+    #[tokio::test]
+    async fn test_schedule_checked_requires_capability() {
+        let storage = Arc::new(Mutex::new(WorldStorage::in_memory().await.unwrap()));
+        let scheduler = Scheduler::new(Arc::clone(&storage), 100);
+
+        let entity_id = {
+            let storage = storage.lock().await;
+            storage
+                .create_entity(serde_json::json!({"name": "Test"}), None)
+                .await
+                .unwrap()
+        };
+
+        let result = scheduler
+            .schedule_checked(entity_id, "tick", serde_json::json!([]), 1_000, 0)
+            .await;
+        assert!(matches!(result, Err(SchedulerError::Unauthorized(id)) if id == entity_id));
+        assert!(scheduler.list(entity_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_schedule_checked_rejects_expired_capability() {
+        let storage = Arc::new(Mutex::new(WorldStorage::in_memory().await.unwrap()));
+        let scheduler = Scheduler::new(Arc::clone(&storage), 100);
+
+        let (expired_id, read_only_id) = {
+            let storage = storage.lock().await;
+            let expired = storage
+                .create_entity(serde_json::json!({"name": "Expired"}), None)
+                .await
+                .unwrap();
+            storage
+                .create_capability(
+                    expired,
+                    cap_types::SCHEDULER,
+                    serde_json::json!({"expires_at": 1}),
+                )
+                .await
+                .unwrap();
+            let read_only = storage
+                .create_entity(serde_json::json!({"name": "ReadOnly"}), None)
+                .await
+                .unwrap();
+            storage
+                .create_capability(
+                    read_only,
+                    cap_types::SCHEDULER,
+                    serde_json::json!({"ops": ["list"]}),
+                )
+                .await
+                .unwrap();
+            (expired, read_only)
+        };
+
+        let result = scheduler
+            .schedule_checked(expired_id, "tick", serde_json::json!([]), 1_000, 0)
+            .await;
+        assert!(matches!(
+            result,
+            Err(SchedulerError::Capability(CapabilityError::Expired { .. }))
+        ));
+        let result = scheduler
+            .schedule_checked(read_only_id, "tick", serde_json::json!([]), 1_000, 0)
+            .await;
+        assert!(matches!(
+            result,
+            Err(SchedulerError::Capability(
+                CapabilityError::OperationNotAllowed { .. }
+            ))
+        ));
+        assert!(scheduler.list(expired_id).await.unwrap().is_empty());
+        assert!(scheduler.list(read_only_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_schedule_checked_enforces_limits() {
+        let storage = Arc::new(Mutex::new(WorldStorage::in_memory().await.unwrap()));
+        let scheduler = Scheduler::new(Arc::clone(&storage), 100);
+
+        let entity_id = {
+            let storage = storage.lock().await;
+            let id = storage
+                .create_entity(serde_json::json!({"name": "Test"}), None)
+                .await
+                .unwrap();
+            storage
+                .create_capability(
+                    id,
+                    cap_types::SCHEDULER,
+                    serde_json::json!({"max_tasks": 2, "min_interval_ms": 500}),
+                )
+                .await
+                .unwrap();
+            id
+        };
+
+        // Too soon
+        let result = scheduler
+            .schedule_checked(entity_id, "tick", serde_json::json!([]), 100, 0)
+            .await;
+        assert!(matches!(result, Err(SchedulerError::LimitExceeded(_))));
+
+        // Within limits, up to max_tasks
+        for _ in 0..2 {
+            scheduler
+                .schedule_checked(entity_id, "tick", serde_json::json!([]), 1_000, 0)
+                .await
+                .unwrap();
+        }
+        let result = scheduler
+            .schedule_checked(entity_id, "tick", serde_json::json!([]), 1_000, 0)
+            .await;
+        assert!(matches!(result, Err(SchedulerError::LimitExceeded(_))));
+        assert_eq!(scheduler.list(entity_id).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_schedule_checked_rejects_out_of_range_delay() {
+        let storage = Arc::new(Mutex::new(WorldStorage::in_memory().await.unwrap()));
+        let scheduler = Scheduler::new(Arc::clone(&storage), 100);
+
+        let entity_id = {
+            let storage = storage.lock().await;
+            let id = storage
+                .create_entity(serde_json::json!({"name": "Test"}), None)
+                .await
+                .unwrap();
+            storage
+                .create_capability(
+                    id,
+                    cap_types::SCHEDULER,
+                    serde_json::json!({"min_interval_ms": 60_000}),
+                )
+                .await
+                .unwrap();
+            id
+        };
+
+        // Neither may panic or wrap around into a task that is already due
+        for delay_ms in [u64::MAX, i64::MAX as u64] {
+            let result = scheduler
+                .schedule_checked(entity_id, "tick", serde_json::json!([]), delay_ms, 0)
+                .await;
+            assert!(
+                matches!(result, Err(SchedulerError::LimitExceeded(_))),
+                "delay {} was accepted",
+                delay_ms
+            );
+        }
+        assert!(scheduler.list(entity_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_emit_queues_subscribed_handlers() {
+        let storage = Arc::new(Mutex::new(WorldStorage::in_memory().await.unwrap()));
+        let scheduler = Scheduler::new(Arc::clone(&storage), 100);
+
+        let (note, watcher, indexer) = {
+            let storage = storage.lock().await;
+            let note = storage
+                .create_entity(serde_json::json!({"name": "Note"}), None)
+                .await
+                .unwrap();
+            let watcher = storage
+                .create_entity(serde_json::json!({"name": "Watcher"}), None)
+                .await
+                .unwrap();
+            let indexer = storage
+                .create_entity(serde_json::json!({"name": "Indexer"}), None)
+                .await
+                .unwrap();
+            storage
+                .subscribe(note, "changed", watcher, "on_change")
+                .await
+                .unwrap();
+            storage
+                .subscribe(note, "changed", indexer, "reindex")
+                .await
+                .unwrap();
+            storage
+                .subscribe(note, "deleted", watcher, "on_delete")
+                .await
+                .unwrap();
+            (note, watcher, indexer)
+        };
+
+        let task_ids = scheduler
+            .emit(note, "changed", serde_json::json!({"field": "body"}))
+            .await
+            .unwrap();
+        assert_eq!(task_ids.len(), 2);
+
+        let tasks = scheduler.list(watcher).await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].verb, "on_change");
+        assert_eq!(
+            tasks[0].args,
+            serde_json::json!([note, "changed", {"field": "body"}])
+        );
+        assert_eq!(scheduler.list(indexer).await.unwrap()[0].verb, "reindex");
+
+        // Events nobody listens to queue nothing
+        assert!(
+            scheduler
+                .emit(note, "renamed", serde_json::json!(null))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
@@ -1,9 +1,17 @@
 //! SQLite storage layer.
 
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
 use libsql::{Connection, Database, params};
 use thiserror::Error;
 
-use crate::entity::{Entity, EntityId, Verb};
+use crate::entity::{Entity, EntityId, EntityReference, EventSubscription, Verb};
+
+mod pool;
+
+use pool::{ReadConnection, ReadPool};
 
 #[derive(Debug, Error)]
 pub enum StorageError {
@@ -18,44 +26,249 @@ pub enum StorageError {
 
     #[error("transaction error: {0}")]
     Transaction(String),
+
+    #[error("encryption error: {0}")]
+    Encryption(String),
+
+    #[error("invalid JSON pointer: {0}")]
+    InvalidPointer(String),
+
+    #[error("capability not found: {0}")]
+    CapabilityNotFound(String),
+
+    #[error("entity {entity_id} does not own capability {capability_id}")]
+    NotCapabilityOwner {
+        capability_id: String,
+        entity_id: EntityId,
+    },
+
+    #[error(transparent)]
+    Capability(#[from] crate::CapabilityError),
+}
+
+/// Prefix marking an encrypted column value (base64 of nonce || ciphertext).
+const ENCRYPTED_PREFIX: &str = "enc:";
+
+/// Options for opening a [`WorldStorage`].
+#[derive(Clone)]
+pub struct StorageOptions {
+    /// Key for at-rest encryption of capability params.
+    ///
+    /// See [`WorldStorage::open_encrypted`].
+    pub encryption_key: Option<[u8; 32]>,
+    /// Number of pooled read connections.
+    ///
+    /// `0` (the default) routes everything through one connection. Otherwise
+    /// the database is switched to WAL mode and reads outside a transaction
+    /// use the pool, so they run in parallel with each other and with the
+    /// writer. Writes always go through the single writer connection. Skipped,
+    /// with a warning, for in-memory and temporary databases, which can't be
+    /// shared between connections.
+    pub read_pool_size: usize,
+    /// How long a connection waits on a locked database before failing
+    /// with `SQLITE_BUSY`, in milliseconds.
+    pub busy_timeout_ms: u64,
+}
+
+impl Default for StorageOptions {
+    fn default() -> Self {
+        Self {
+            encryption_key: None,
+            read_pool_size: 0,
+            busy_timeout_ms: 5_000,
+        }
+    }
+}
+
+/// Outcome of a WAL checkpoint.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    /// Whether the checkpoint was blocked by readers or writers and could
+    /// not finish.
+    pub busy: bool,
+    /// Frames in the WAL before the checkpoint (`-1` when not in WAL mode).
+    pub wal_frames: i64,
+    /// Frames copied back into the database (`-1` when not in WAL mode).
+    pub checkpointed_frames: i64,
 }
 
 /// World storage backed by libSQL.
 pub struct WorldStorage {
     conn: Connection,
-    #[allow(dead_code)]
     db: Database,
     /// Transaction depth for nested savepoints.
     transaction_depth: usize,
+    /// Cipher for at-rest encryption of sensitive columns, if enabled.
+    cipher: Option<XChaCha20Poly1305>,
+    /// Pooled read connections, if enabled.
+    read_pool: Option<ReadPool>,
 }
 
 impl WorldStorage {
     /// Open or create a world database.
     pub async fn open(path: &str) -> Result<Self, StorageError> {
-        let db = libsql::Builder::new_local(path).build().await?;
-        let conn = db.connect()?;
-        let storage = Self {
-            conn,
-            db,
-            transaction_depth: 0,
+        Self::open_with(path, &StorageOptions::default()).await
+    }
+
+    /// Open or create a world database with capability params encrypted at rest.
+    ///
+    /// Params are sealed with XChaCha20-Poly1305 under `key`; everything else
+    /// stays plaintext so it remains queryable. The key is never written to
+    /// the database. Plaintext params from before encryption was enabled are
+    /// still readable.
+    pub async fn open_encrypted(path: &str, key: &[u8; 32]) -> Result<Self, StorageError> {
+        let options = StorageOptions {
+            encryption_key: Some(*key),
+            ..Default::default()
         };
-        storage.init_schema().await?;
-        Ok(storage)
+        Self::open_with(path, &options).await
     }
 
     /// Open an in-memory database.
     pub async fn in_memory() -> Result<Self, StorageError> {
-        let db = libsql::Builder::new_local(":memory:").build().await?;
+        Self::open_with(":memory:", &StorageOptions::default()).await
+    }
+
+    /// Open or create a world database with explicit options.
+    pub async fn open_with(path: &str, options: &StorageOptions) -> Result<Self, StorageError> {
+        let db = libsql::Builder::new_local(path).build().await?;
         let conn = db.connect()?;
-        let storage = Self {
+        let cipher = options
+            .encryption_key
+            .map(|key| XChaCha20Poly1305::new(&key.into()));
+        set_busy_timeout(&conn, options.busy_timeout_ms).await?;
+        let mut storage = Self {
             conn,
             db,
             transaction_depth: 0,
+            cipher,
+            read_pool: None,
         };
         storage.init_schema().await?;
+
+        if options.read_pool_size > 0 {
+            // Readers only run alongside the writer under WAL. SQLite reports
+            // the mode it actually chose; in-memory and temporary databases
+            // (":memory:", "", memory URIs) stay out of WAL, and each new
+            // connection to them would see a separate empty database.
+            let mut rows = storage.conn.query("PRAGMA journal_mode = WAL", ()).await?;
+            let mode: String = match rows.next().await? {
+                Some(row) => row.get(0)?,
+                None => String::new(),
+            };
+            if mode.eq_ignore_ascii_case("wal") {
+                storage.read_pool = Some(
+                    ReadPool::new(&storage.db, options.read_pool_size, options.busy_timeout_ms)
+                        .await?,
+                );
+            } else {
+                eprintln!(
+                    "[Storage] Ignoring read_pool_size {}: {:?} can't be shared between connections (journal mode {:?})",
+                    options.read_pool_size, path, mode
+                );
+            }
+        }
         Ok(storage)
     }
 
+    /// Checkpoint the WAL into the main database file and truncate it.
+    ///
+    /// SQLite's automatic checkpoints never shrink the `-wal` file, so
+    /// long-running servers should call this periodically (e.g. from the
+    /// scheduler loop). A no-op for databases not in WAL mode.
+    pub async fn checkpoint(&self) -> Result<Checkpoint, StorageError> {
+        let mut rows = self
+            .conn
+            .query("PRAGMA wal_checkpoint(TRUNCATE)", ())
+            .await?;
+        let Some(row) = rows.next().await? else {
+            return Ok(Checkpoint {
+                busy: false,
+                wal_frames: -1,
+                checkpointed_frames: -1,
+            });
+        };
+        let busy: i64 = row.get(0)?;
+        Ok(Checkpoint {
+            busy: busy != 0,
+            wal_frames: row.get(1)?,
+            checkpointed_frames: row.get(2)?,
+        })
+    }
+
+    /// Get a connection for a read.
+    ///
+    /// Inside a transaction this is always the writer, so reads see the
+    /// transaction's own uncommitted changes.
+    async fn reader(&self) -> ReadConnection<'_> {
+        match &self.read_pool {
+            Some(pool) if self.transaction_depth == 0 => ReadConnection::Pooled(pool.get().await),
+            _ => ReadConnection::Writer(&self.conn),
+        }
+    }
+
+    // =========================================================================
+    // Encryption
+    // =========================================================================
+
+    /// Serialize a JSON value for a sensitive column, encrypting if enabled.
+    ///
+    /// `aad` identifies the row the value belongs to. It is authenticated but
+    /// not stored, so a sealed value copied onto another row fails to open.
+    fn seal_json(&self, value: &serde_json::Value, aad: &[u8]) -> Result<String, StorageError> {
+        let plaintext = serde_json::to_string(value)?;
+        let Some(cipher) = &self.cipher else {
+            return Ok(plaintext);
+        };
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext.as_bytes(),
+                    aad,
+                },
+            )
+            .map_err(|e| StorageError::Encryption(e.to_string()))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}", ENCRYPTED_PREFIX, BASE64.encode(sealed)))
+    }
+
+    /// Parse a sensitive column value, decrypting if it was sealed.
+    ///
+    /// `aad` must match what the value was sealed with.
+    fn open_json(&self, stored: &str, aad: &[u8]) -> Result<serde_json::Value, StorageError> {
+        let Some(encoded) = stored.strip_prefix(ENCRYPTED_PREFIX) else {
+            return Ok(serde_json::from_str(stored)?);
+        };
+        let cipher = self.cipher.as_ref().ok_or_else(|| {
+            StorageError::Encryption("value is encrypted but no key was provided".to_string())
+        })?;
+        let sealed = BASE64
+            .decode(encoded)
+            .map_err(|e| StorageError::Encryption(e.to_string()))?;
+        // XChaCha20 nonces are 24 bytes
+        if sealed.len() < 24 {
+            return Err(StorageError::Encryption("truncated ciphertext".to_string()));
+        }
+        let (nonce, ciphertext) = sealed.split_at(24);
+        let plaintext = cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| {
+                StorageError::Encryption(
+                    "decryption failed (wrong key, or value moved from another row?)".to_string(),
+                )
+            })?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
     // =========================================================================
     // Transaction Management
     // =========================================================================
@@ -122,6 +335,26 @@ impl WorldStorage {
         Ok(())
     }
 
+    /// Run a closure inside a transaction.
+    ///
+    /// Commits if the closure returns `Ok`, rolls back if it returns `Err`.
+    pub async fn transaction<T, F>(&mut self, f: F) -> Result<T, StorageError>
+    where
+        F: AsyncFnOnce(&mut Self) -> Result<T, StorageError>,
+    {
+        self.begin_transaction().await?;
+        match f(self).await {
+            Ok(value) => {
+                self.commit().await?;
+                Ok(value)
+            }
+            Err(e) => {
+                self.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+
     /// Check if currently in a transaction.
     pub fn in_transaction(&self) -> bool {
         self.transaction_depth > 0
@@ -164,6 +397,7 @@ impl WorldStorage {
                 verb TEXT NOT NULL,
                 args TEXT DEFAULT '[]',
                 execute_at INTEGER NOT NULL,
+                priority INTEGER NOT NULL DEFAULT 0,
                 FOREIGN KEY(entity_id) REFERENCES entities(id) ON DELETE CASCADE
             )",
                 (),
@@ -177,6 +411,7 @@ impl WorldStorage {
                 owner_id INTEGER NOT NULL,
                 type TEXT NOT NULL,
                 params TEXT NOT NULL,
+                parent_id TEXT,
                 FOREIGN KEY(owner_id) REFERENCES entities(id) ON DELETE CASCADE
             )",
                 (),
@@ -190,6 +425,94 @@ impl WorldStorage {
             )
             .await?;
 
+        self.conn
+            .execute(
+                "CREATE INDEX IF NOT EXISTS idx_entities_prototype ON entities(prototype_id)",
+                (),
+            )
+            .await?;
+
+        self.conn
+            .execute(
+                "CREATE TABLE IF NOT EXISTS entity_references (
+                source_id INTEGER NOT NULL,
+                target_id INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                PRIMARY KEY(source_id, target_id, kind),
+                FOREIGN KEY(source_id) REFERENCES entities(id) ON DELETE CASCADE
+            )",
+                (),
+            )
+            .await?;
+
+        self.conn
+            .execute(
+                "CREATE INDEX IF NOT EXISTS idx_entity_references_target ON entity_references(target_id)",
+                (),
+            )
+            .await?;
+
+        self.conn
+            .execute(
+                "CREATE TABLE IF NOT EXISTS event_subscriptions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                source_id INTEGER NOT NULL,
+                event TEXT NOT NULL,
+                handler_id INTEGER NOT NULL,
+                handler_verb TEXT NOT NULL,
+                UNIQUE(source_id, event, handler_id, handler_verb),
+                FOREIGN KEY(source_id) REFERENCES entities(id) ON DELETE CASCADE,
+                FOREIGN KEY(handler_id) REFERENCES entities(id) ON DELETE CASCADE
+            )",
+                (),
+            )
+            .await?;
+
+        self.conn
+            .execute(
+                "CREATE INDEX IF NOT EXISTS idx_event_subscriptions_handler ON event_subscriptions(handler_id)",
+                (),
+            )
+            .await?;
+
+        // Columns added after the original schema, for existing databases
+        self.add_column_if_missing("scheduled_tasks", "priority", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing("capabilities", "parent_id", "TEXT")
+            .await?;
+
+        self.conn
+            .execute(
+                "CREATE INDEX IF NOT EXISTS idx_capabilities_parent ON capabilities(parent_id)",
+                (),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Add a column to an existing table unless it is already there.
+    async fn add_column_if_missing(
+        &self,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<(), StorageError> {
+        let mut rows = self
+            .conn
+            .query(
+                "SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2",
+                params![table, column],
+            )
+            .await?;
+        if rows.next().await?.is_none() {
+            self.conn
+                .execute(
+                    &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                    (),
+                )
+                .await?;
+        }
         Ok(())
     }
 
@@ -211,8 +534,8 @@ impl WorldStorage {
 
     /// Get an entity by ID (raw, without prototype resolution).
     pub async fn get_entity_raw(&self, id: EntityId) -> Result<Option<Entity>, StorageError> {
-        let mut rows = self
-            .conn
+        let conn = self.reader().await;
+        let mut rows = conn
             .query(
                 "SELECT id, prototype_id, props FROM entities WHERE id = ?1",
                 params![id],
@@ -237,8 +560,8 @@ impl WorldStorage {
     /// Get an entity with resolved prototype chain properties.
     pub async fn get_entity(&self, id: EntityId) -> Result<Option<Entity>, StorageError> {
         // Use CTE to get the entire prototype chain
-        let mut rows = self
-            .conn
+        let conn = self.reader().await;
+        let mut rows = conn
             .query(
                 r#"
             WITH RECURSIVE lineage AS (
@@ -282,6 +605,105 @@ impl WorldStorage {
         }))
     }
 
+    /// List entities whose direct prototype is `prototype_id`, one page at a time.
+    ///
+    /// Ids are returned in creation order (ids are AUTOINCREMENT, so they never
+    /// go backwards). `total` is the count across all pages.
+    pub async fn entities_by_prototype(
+        &self,
+        prototype_id: EntityId,
+        offset: u64,
+        limit: u64,
+    ) -> Result<EntityPage, StorageError> {
+        let conn = self.reader().await;
+        let mut rows = conn
+            .query(
+                "SELECT COUNT(*) FROM entities WHERE prototype_id = ?1",
+                params![prototype_id],
+            )
+            .await?;
+        let total: u64 = match rows.next().await? {
+            Some(row) => row.get(0)?,
+            None => 0,
+        };
+
+        // SQLite takes i64; anything past i64::MAX means the same as i64::MAX
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let offset = i64::try_from(offset).unwrap_or(i64::MAX);
+        let mut rows = conn
+            .query(
+                "SELECT id FROM entities WHERE prototype_id = ?1 ORDER BY id ASC LIMIT ?2 OFFSET ?3",
+                params![prototype_id, limit, offset],
+            )
+            .await?;
+
+        let mut ids = Vec::new();
+        while let Some(row) = rows.next().await? {
+            ids.push(row.get(0)?);
+        }
+
+        Ok(EntityPage { ids, total })
+    }
+
+    /// Bytes of storage attributable to an entity.
+    ///
+    /// Sums the entity's own props JSON plus everything it owns: verbs (name,
+    /// code, required capability), capabilities (id, type, params) and
+    /// scheduled tasks (verb, args). Computed with SQL aggregates, so nothing
+    /// is loaded into memory. Excludes index and page overhead.
+    pub async fn entity_storage_bytes(&self, id: EntityId) -> Result<u64, StorageError> {
+        let conn = self.reader().await;
+        let mut rows = conn
+            .query(
+                r#"
+            SELECT
+                length(CAST(e.props AS BLOB))
+                + (SELECT COALESCE(SUM(length(CAST(name AS BLOB)) + length(CAST(code AS BLOB))
+                    + COALESCE(length(CAST(required_capability AS BLOB)), 0)), 0)
+                    FROM verbs WHERE entity_id = e.id)
+                + (SELECT COALESCE(SUM(length(CAST(id AS BLOB)) + length(CAST(type AS BLOB))
+                    + length(CAST(params AS BLOB))), 0)
+                    FROM capabilities WHERE owner_id = e.id)
+                + (SELECT COALESCE(SUM(length(CAST(verb AS BLOB)) + length(CAST(args AS BLOB))), 0)
+                    FROM scheduled_tasks WHERE entity_id = e.id)
+            FROM entities e WHERE e.id = ?1
+            "#,
+                params![id],
+            )
+            .await?;
+
+        match rows.next().await? {
+            Some(row) => Ok(row.get(0)?),
+            None => Err(StorageError::EntityNotFound(id)),
+        }
+    }
+
+    /// Bytes of storage used by the whole world, counted the same way as
+    /// [`Self::entity_storage_bytes`].
+    pub async fn total_storage_bytes(&self) -> Result<u64, StorageError> {
+        let conn = self.reader().await;
+        let mut rows = conn
+            .query(
+                r#"
+            SELECT
+                (SELECT COALESCE(SUM(length(CAST(props AS BLOB))), 0) FROM entities)
+                + (SELECT COALESCE(SUM(length(CAST(name AS BLOB)) + length(CAST(code AS BLOB))
+                    + COALESCE(length(CAST(required_capability AS BLOB)), 0)), 0) FROM verbs)
+                + (SELECT COALESCE(SUM(length(CAST(id AS BLOB)) + length(CAST(type AS BLOB))
+                    + length(CAST(params AS BLOB))), 0) FROM capabilities)
+                + (SELECT COALESCE(SUM(length(CAST(verb AS BLOB)) + length(CAST(args AS BLOB))), 0)
+                    FROM scheduled_tasks)
+            "#,
+                (),
+            )
+            .await?;
+
+        match rows.next().await? {
+            Some(row) => Ok(row.get(0)?),
+            None => Ok(0),
+        }
+    }
+
     /// Update an entity's properties.
     pub async fn update_entity(
         &self,
@@ -313,6 +735,85 @@ impl WorldStorage {
         Ok(())
     }
 
+    /// Set a single (possibly nested) property without rewriting the rest.
+    ///
+    /// `pointer` is an RFC 6901 JSON pointer into the entity's own props
+    /// (e.g. `/stats/hp`); every segment addresses an object key, and missing
+    /// intermediate objects are created. The change is applied in SQL via
+    /// `json_set`, so concurrent patches to different keys don't race.
+    ///
+    /// A pointer that passes through an array or scalar (e.g. `/tags/0`) is
+    /// rejected with [`StorageError::InvalidPointer`].
+    pub async fn patch_entity(
+        &self,
+        id: EntityId,
+        pointer: &str,
+        value: &serde_json::Value,
+    ) -> Result<(), StorageError> {
+        let path = json_pointer_to_path(pointer)?;
+        let value_str = serde_json::to_string(value)?;
+        // json_set silently leaves props unchanged when the path runs through
+        // a non-object; after a real set the path always resolves.
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE entities SET props = json_set(props, ?1, json(?2))
+                 WHERE id = ?3 AND json_type(json_set(props, ?1, json(?2)), ?1) IS NOT NULL",
+                params![path, value_str, id],
+            )
+            .await?;
+        if updated == 0 {
+            return Err(self.unapplied_pointer_error(id, pointer).await);
+        }
+        Ok(())
+    }
+
+    /// Remove a single (possibly nested) property, leaving siblings untouched.
+    ///
+    /// `pointer` follows the same rules as [`Self::patch_entity`], including
+    /// rejecting paths through arrays or scalars. Removing a key that doesn't
+    /// exist is not an error.
+    pub async fn remove_entity_prop(
+        &self,
+        id: EntityId,
+        pointer: &str,
+    ) -> Result<(), StorageError> {
+        let path = json_pointer_to_path(pointer)?;
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE entities SET props = json_remove(props, ?1)
+                 WHERE id = ?2 AND json_type(json_set(props, ?1, NULL), ?1) IS NOT NULL",
+                params![path, id],
+            )
+            .await?;
+        if updated == 0 {
+            return Err(self.unapplied_pointer_error(id, pointer).await);
+        }
+        Ok(())
+    }
+
+    /// Explain why a pointer update matched no rows: either the entity is
+    /// missing or the pointer runs through a non-object value.
+    async fn unapplied_pointer_error(&self, id: EntityId, pointer: &str) -> StorageError {
+        let exists = match self
+            .conn
+            .query("SELECT 1 FROM entities WHERE id = ?1", params![id])
+            .await
+        {
+            Ok(mut rows) => rows.next().await.map(|row| row.is_some()),
+            Err(e) => Err(e),
+        };
+        match exists {
+            Ok(true) => StorageError::InvalidPointer(format!(
+                "{} (passes through a non-object value)",
+                pointer
+            )),
+            Ok(false) => StorageError::EntityNotFound(id),
+            Err(e) => e.into(),
+        }
+    }
+
     /// Set an entity's prototype.
     pub async fn set_prototype(
         &self,
@@ -333,8 +834,32 @@ impl WorldStorage {
         self.conn
             .execute("DELETE FROM verbs WHERE entity_id = ?1", params![id])
             .await?;
+        // Revokes everything delegated from the entity's capabilities too
         self.conn
-            .execute("DELETE FROM capabilities WHERE owner_id = ?1", params![id])
+            .execute(
+                "DELETE FROM capabilities WHERE id IN (
+                    WITH RECURSIVE tree(id) AS (
+                        SELECT id FROM capabilities WHERE owner_id = ?1
+                        UNION
+                        SELECT c.id FROM capabilities c JOIN tree t ON c.parent_id = t.id
+                    )
+                    SELECT id FROM tree
+                )",
+                params![id],
+            )
+            .await?;
+        // Outgoing references only; incoming ones dangle until gc_dangling_references
+        self.conn
+            .execute(
+                "DELETE FROM entity_references WHERE source_id = ?1",
+                params![id],
+            )
+            .await?;
+        self.conn
+            .execute(
+                "DELETE FROM event_subscriptions WHERE source_id = ?1 OR handler_id = ?1",
+                params![id],
+            )
             .await?;
         self.conn
             .execute("DELETE FROM entities WHERE id = ?1", params![id])
@@ -374,8 +899,8 @@ impl WorldStorage {
         entity_id: EntityId,
         name: &str,
     ) -> Result<Option<Verb>, StorageError> {
-        let mut rows = self
-            .conn
+        let conn = self.reader().await;
+        let mut rows = conn
             .query(
                 r#"
             WITH RECURSIVE lineage AS (
@@ -417,8 +942,8 @@ impl WorldStorage {
 
     /// Get all verbs for an entity (including inherited).
     pub async fn get_verbs(&self, entity_id: EntityId) -> Result<Vec<Verb>, StorageError> {
-        let mut rows = self
-            .conn
+        let conn = self.reader().await;
+        let mut rows = conn
             .query(
                 r#"
             WITH RECURSIVE lineage AS (
@@ -462,11 +987,7 @@ impl WorldStorage {
     }
 
     /// Update a verb's code.
-    pub async fn update_verb(
-        &self,
-        id: i64,
-        code: &serde_json::Value,
-    ) -> Result<(), StorageError> {
+    pub async fn update_verb(&self, id: i64, code: &serde_json::Value) -> Result<(), StorageError> {
         let code_str = serde_json::to_string(code)?;
         self.conn
             .execute(
@@ -497,7 +1018,7 @@ impl WorldStorage {
         params: serde_json::Value,
     ) -> Result<String, StorageError> {
         let id = uuid::Uuid::new_v4().to_string();
-        let params_str = serde_json::to_string(&params)?;
+        let params_str = self.seal_json(&params, &capability_aad(&id, cap_type))?;
         self.conn
             .execute(
                 "INSERT INTO capabilities (id, owner_id, type, params) VALUES (?1, ?2, ?3, ?4)",
@@ -507,15 +1028,94 @@ impl WorldStorage {
         Ok(id)
     }
 
+    /// Delegate a narrowed copy of `delegator_id`'s capability to `owner_id`.
+    ///
+    /// The delegator must own `parent_id`, and the parent must pass
+    /// [`crate::Capability::check_use`] for the `"delegate"` op at `now_ms`
+    /// (so an expired capability can't be handed on, and one with an `ops`
+    /// list must include `"delegate"`). The new capability has the same type,
+    /// with params narrowed by `restrictions` (see
+    /// [`crate::Capability::narrow`]). Revoking the parent later revokes the
+    /// delegated copy too.
+    pub async fn delegate_capability(
+        &self,
+        parent_id: &str,
+        delegator_id: EntityId,
+        owner_id: EntityId,
+        restrictions: &serde_json::Value,
+        now_ms: i64,
+    ) -> Result<String, StorageError> {
+        let parent = self
+            .get_capability(parent_id)
+            .await?
+            .ok_or_else(|| StorageError::CapabilityNotFound(parent_id.to_string()))?;
+        if parent.owner_id != delegator_id {
+            return Err(StorageError::NotCapabilityOwner {
+                capability_id: parent_id.to_string(),
+                entity_id: delegator_id,
+            });
+        }
+        parent.check_use("delegate", now_ms)?;
+        let params = parent.narrow(restrictions)?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let params_str = self.seal_json(&params, &capability_aad(&id, &parent.cap_type))?;
+        // Re-check the parent in the same statement: if it was revoked or
+        // handed off since we read it, a child inserted anyway would point at
+        // a missing parent and escape every later cascade.
+        let inserted = self
+            .conn
+            .execute(
+                "INSERT INTO capabilities (id, owner_id, type, params, parent_id)
+                 SELECT ?1, ?2, ?3, ?4, ?5
+                 WHERE EXISTS (SELECT 1 FROM capabilities WHERE id = ?5 AND owner_id = ?6)",
+                libsql::params![
+                    id.clone(),
+                    owner_id,
+                    parent.cap_type,
+                    params_str,
+                    parent_id,
+                    delegator_id
+                ],
+            )
+            .await?;
+        if inserted == 0 {
+            return Err(StorageError::CapabilityNotFound(parent_id.to_string()));
+        }
+        Ok(id)
+    }
+
+    /// Create several capabilities for one owner atomically.
+    ///
+    /// Either every capability is created or none are. Returns the new ids in
+    /// the same order as `specs`.
+    pub async fn grant_capabilities(
+        &mut self,
+        owner_id: EntityId,
+        specs: &[CapabilitySpec],
+    ) -> Result<Vec<String>, StorageError> {
+        self.transaction(async |s| {
+            let mut ids = Vec::with_capacity(specs.len());
+            for spec in specs {
+                ids.push(
+                    s.create_capability(owner_id, &spec.cap_type, spec.params.clone())
+                        .await?,
+                );
+            }
+            Ok(ids)
+        })
+        .await
+    }
+
     /// Get a capability by ID.
     pub async fn get_capability(
         &self,
         id: &str,
     ) -> Result<Option<crate::Capability>, StorageError> {
-        let mut rows = self
-            .conn
+        let conn = self.reader().await;
+        let mut rows = conn
             .query(
-                "SELECT id, owner_id, type, params FROM capabilities WHERE id = ?1",
+                "SELECT id, owner_id, type, params, parent_id FROM capabilities WHERE id = ?1",
                 params![id],
             )
             .await?;
@@ -525,12 +1125,14 @@ impl WorldStorage {
             let owner_id: EntityId = row.get(1)?;
             let cap_type: String = row.get(2)?;
             let params_str: String = row.get(3)?;
-            let params: serde_json::Value = serde_json::from_str(&params_str)?;
+            let params = self.open_json(&params_str, &capability_aad(&id, &cap_type))?;
+            let parent_id: Option<String> = row.get(4)?;
             Ok(Some(crate::Capability {
                 id,
                 owner_id,
                 cap_type,
                 params,
+                parent_id,
             }))
         } else {
             Ok(None)
@@ -542,10 +1144,10 @@ impl WorldStorage {
         &self,
         owner_id: EntityId,
     ) -> Result<Vec<crate::Capability>, StorageError> {
-        let mut rows = self
-            .conn
+        let conn = self.reader().await;
+        let mut rows = conn
             .query(
-                "SELECT id, owner_id, type, params FROM capabilities WHERE owner_id = ?1",
+                "SELECT id, owner_id, type, params, parent_id FROM capabilities WHERE owner_id = ?1",
                 params![owner_id],
             )
             .await?;
@@ -556,12 +1158,14 @@ impl WorldStorage {
             let owner_id: EntityId = row.get(1)?;
             let cap_type: String = row.get(2)?;
             let params_str: String = row.get(3)?;
-            let params: serde_json::Value = serde_json::from_str(&params_str)?;
+            let params = self.open_json(&params_str, &capability_aad(&id, &cap_type))?;
+            let parent_id: Option<String> = row.get(4)?;
             caps.push(crate::Capability {
                 id,
                 owner_id,
                 cap_type,
                 params,
+                parent_id,
             });
         }
 
@@ -583,14 +1187,222 @@ impl WorldStorage {
         Ok(())
     }
 
-    /// Delete a capability.
-    pub async fn delete_capability(&self, id: &str) -> Result<(), StorageError> {
+    /// Revoke a capability and everything delegated from it, transitively.
+    ///
+    /// Returns the number of capabilities removed.
+    pub async fn revoke_capability(&self, id: &str) -> Result<u64, StorageError> {
+        let removed = self
+            .conn
+            .execute(
+                "DELETE FROM capabilities WHERE id IN (
+                    WITH RECURSIVE tree(id) AS (
+                        SELECT ?1
+                        UNION
+                        SELECT c.id FROM capabilities c JOIN tree t ON c.parent_id = t.id
+                    )
+                    SELECT id FROM tree
+                )",
+                params![id],
+            )
+            .await?;
+        Ok(removed)
+    }
+
+    // =========================================================================
+    // References
+    // =========================================================================
+
+    /// Record that `source_id` refers to `target_id` (e.g. a wikilink or owner).
+    ///
+    /// Adding an edge that already exists is a no-op.
+    pub async fn add_reference(
+        &self,
+        source_id: EntityId,
+        target_id: EntityId,
+        kind: &str,
+    ) -> Result<(), StorageError> {
+        self.conn
+            .execute(
+                "INSERT OR IGNORE INTO entity_references (source_id, target_id, kind) VALUES (?1, ?2, ?3)",
+                params![source_id, target_id, kind],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Remove a reference edge.
+    pub async fn remove_reference(
+        &self,
+        source_id: EntityId,
+        target_id: EntityId,
+        kind: &str,
+    ) -> Result<(), StorageError> {
         self.conn
-            .execute("DELETE FROM capabilities WHERE id = ?1", params![id])
+            .execute(
+                "DELETE FROM entity_references WHERE source_id = ?1 AND target_id = ?2 AND kind = ?3",
+                params![source_id, target_id, kind],
+            )
             .await?;
         Ok(())
     }
 
+    /// Get all references from an entity.
+    pub async fn get_references(
+        &self,
+        source_id: EntityId,
+    ) -> Result<Vec<EntityReference>, StorageError> {
+        self.query_references(
+            "SELECT source_id, target_id, kind FROM entity_references WHERE source_id = ?1 ORDER BY target_id, kind",
+            params![source_id],
+        )
+        .await
+    }
+
+    /// Get all references pointing at an entity (backlinks).
+    pub async fn get_backlinks(
+        &self,
+        target_id: EntityId,
+    ) -> Result<Vec<EntityReference>, StorageError> {
+        self.query_references(
+            "SELECT source_id, target_id, kind FROM entity_references WHERE target_id = ?1 ORDER BY source_id, kind",
+            params![target_id],
+        )
+        .await
+    }
+
+    /// Find references whose target entity no longer exists, without
+    /// modifying anything.
+    pub async fn validate_integrity(&self) -> Result<Vec<EntityReference>, StorageError> {
+        self.query_references(
+            "SELECT r.source_id, r.target_id, r.kind FROM entity_references r
+            LEFT JOIN entities e ON e.id = r.target_id
+            WHERE e.id IS NULL
+            ORDER BY r.source_id, r.target_id, r.kind",
+            (),
+        )
+        .await
+    }
+
+    /// Delete references whose target entity no longer exists.
+    ///
+    /// Returns the edges that were removed.
+    pub async fn gc_dangling_references(&mut self) -> Result<Vec<EntityReference>, StorageError> {
+        self.transaction(async |s| {
+            let dangling = s.validate_integrity().await?;
+            s.conn
+                .execute(
+                    "DELETE FROM entity_references
+                    WHERE target_id NOT IN (SELECT id FROM entities)",
+                    (),
+                )
+                .await?;
+            Ok(dangling)
+        })
+        .await
+    }
+
+    async fn query_references(
+        &self,
+        sql: &str,
+        params: impl libsql::params::IntoParams,
+    ) -> Result<Vec<EntityReference>, StorageError> {
+        let conn = self.reader().await;
+        let mut rows = conn.query(sql, params).await?;
+
+        let mut refs = Vec::new();
+        while let Some(row) = rows.next().await? {
+            refs.push(EntityReference {
+                source_id: row.get(0)?,
+                target_id: row.get(1)?,
+                kind: row.get(2)?,
+            });
+        }
+
+        Ok(refs)
+    }
+
+    // =========================================================================
+    // Events
+    // =========================================================================
+
+    /// Subscribe `handler_id`'s verb to `event` emitted by `source_id`.
+    ///
+    /// Subscribing the same handler twice is a no-op. Returns the
+    /// subscription id either way.
+    pub async fn subscribe(
+        &self,
+        source_id: EntityId,
+        event: &str,
+        handler_id: EntityId,
+        handler_verb: &str,
+    ) -> Result<i64, StorageError> {
+        // The no-op update makes RETURNING yield the existing row's id
+        let mut rows = self
+            .conn
+            .query(
+                "INSERT INTO event_subscriptions (source_id, event, handler_id, handler_verb)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(source_id, event, handler_id, handler_verb)
+                 DO UPDATE SET handler_verb = excluded.handler_verb
+                 RETURNING id",
+                params![source_id, event, handler_id, handler_verb],
+            )
+            .await?;
+        let row = rows
+            .next()
+            .await?
+            .ok_or(StorageError::Database(libsql::Error::QueryReturnedNoRows))?;
+        Ok(row.get(0)?)
+    }
+
+    /// Remove a subscription. Returns whether it existed.
+    pub async fn unsubscribe(
+        &self,
+        source_id: EntityId,
+        event: &str,
+        handler_id: EntityId,
+        handler_verb: &str,
+    ) -> Result<bool, StorageError> {
+        let removed = self
+            .conn
+            .execute(
+                "DELETE FROM event_subscriptions
+                 WHERE source_id = ?1 AND event = ?2 AND handler_id = ?3 AND handler_verb = ?4",
+                params![source_id, event, handler_id, handler_verb],
+            )
+            .await?;
+        Ok(removed > 0)
+    }
+
+    /// Get the handlers subscribed to `event` from `source_id`, oldest first.
+    pub async fn get_subscribers(
+        &self,
+        source_id: EntityId,
+        event: &str,
+    ) -> Result<Vec<EventSubscription>, StorageError> {
+        let conn = self.reader().await;
+        let mut rows = conn
+            .query(
+                "SELECT id, source_id, event, handler_id, handler_verb FROM event_subscriptions
+                 WHERE source_id = ?1 AND event = ?2 ORDER BY id",
+                params![source_id, event],
+            )
+            .await?;
+
+        let mut subs = Vec::new();
+        while let Some(row) = rows.next().await? {
+            subs.push(EventSubscription {
+                id: row.get(0)?,
+                source_id: row.get(1)?,
+                event: row.get(2)?,
+                handler_id: row.get(3)?,
+                handler_verb: row.get(4)?,
+            });
+        }
+
+        Ok(subs)
+    }
+
     // =========================================================================
     // Scheduled Tasks
     // =========================================================================
@@ -602,42 +1414,92 @@ impl WorldStorage {
         verb: &str,
         args: serde_json::Value,
         execute_at: i64,
+    ) -> Result<i64, StorageError> {
+        self.schedule_task_with_priority(entity_id, verb, args, execute_at, 0)
+            .await
+    }
+
+    /// Schedule a task for future execution with a priority.
+    ///
+    /// Higher priorities run first among tasks that are due at the same time.
+    pub async fn schedule_task_with_priority(
+        &self,
+        entity_id: EntityId,
+        verb: &str,
+        args: serde_json::Value,
+        execute_at: i64,
+        priority: i64,
     ) -> Result<i64, StorageError> {
         let args_str = serde_json::to_string(&args)?;
         self.conn.execute(
-            "INSERT INTO scheduled_tasks (entity_id, verb, args, execute_at) VALUES (?1, ?2, ?3, ?4)",
-            params![entity_id, verb, args_str, execute_at],
+            "INSERT INTO scheduled_tasks (entity_id, verb, args, execute_at, priority) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![entity_id, verb, args_str, execute_at, priority],
         ).await?;
         Ok(self.conn.last_insert_rowid())
     }
 
-    /// Get all tasks that are due (execute_at <= now).
-    pub async fn get_due_tasks(&self, now: i64) -> Result<Vec<ScheduledTask>, StorageError> {
-        let mut rows = self.conn.query(
-            "SELECT id, entity_id, verb, args, execute_at FROM scheduled_tasks WHERE execute_at <= ?1 ORDER BY execute_at ASC",
-            params![now],
+    /// Get all tasks that are due (execute_at <= now), most urgent first.
+    ///
+    /// Tasks are ordered by priority, where every `aging_ms` a task has been
+    /// overdue counts as one extra priority level. This keeps a steady stream
+    /// of high-priority work from starving low-priority tasks forever.
+    pub async fn get_due_tasks(
+        &self,
+        now: i64,
+        aging_ms: i64,
+    ) -> Result<Vec<ScheduledTask>, StorageError> {
+        let conn = self.reader().await;
+        let mut rows = conn
+            .query(
+                "SELECT id, entity_id, verb, args, execute_at, priority FROM scheduled_tasks
+            WHERE execute_at <= ?1
+            ORDER BY priority + (?1 - execute_at) / ?2 DESC, execute_at ASC, id ASC",
+                params![now, aging_ms.max(1)],
+            )
+            .await?;
+
+        let mut tasks = Vec::new();
+        while let Some(row) = rows.next().await? {
+            tasks.push(task_from_row(&row)?);
+        }
+
+        Ok(tasks)
+    }
+
+    /// Get all pending tasks for an entity, soonest first.
+    pub async fn get_entity_tasks(
+        &self,
+        entity_id: EntityId,
+    ) -> Result<Vec<ScheduledTask>, StorageError> {
+        let conn = self.reader().await;
+        let mut rows = conn.query(
+            "SELECT id, entity_id, verb, args, execute_at, priority FROM scheduled_tasks WHERE entity_id = ?1 ORDER BY execute_at ASC",
+            params![entity_id],
         ).await?;
 
         let mut tasks = Vec::new();
         while let Some(row) = rows.next().await? {
-            let id: i64 = row.get(0)?;
-            let entity_id: EntityId = row.get(1)?;
-            let verb: String = row.get(2)?;
-            let args_str: String = row.get(3)?;
-            let execute_at: i64 = row.get(4)?;
-            let args: serde_json::Value = serde_json::from_str(&args_str)?;
-            tasks.push(ScheduledTask {
-                id,
-                entity_id,
-                verb,
-                args,
-                execute_at,
-            });
+            tasks.push(task_from_row(&row)?);
         }
 
         Ok(tasks)
     }
 
+    /// Count pending tasks for an entity.
+    pub async fn count_entity_tasks(&self, entity_id: EntityId) -> Result<u64, StorageError> {
+        let conn = self.reader().await;
+        let mut rows = conn
+            .query(
+                "SELECT COUNT(*) FROM scheduled_tasks WHERE entity_id = ?1",
+                params![entity_id],
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => Ok(row.get(0)?),
+            None => Ok(0),
+        }
+    }
+
     /// Delete a scheduled task.
     pub async fn delete_task(&self, id: i64) -> Result<(), StorageError> {
         self.conn
@@ -645,6 +1507,84 @@ impl WorldStorage {
             .await?;
         Ok(())
     }
+
+    /// Delete a scheduled task only if it belongs to `entity_id`.
+    ///
+    /// Returns whether a task was deleted.
+    pub async fn delete_entity_task(
+        &self,
+        entity_id: EntityId,
+        id: i64,
+    ) -> Result<bool, StorageError> {
+        let deleted = self
+            .conn
+            .execute(
+                "DELETE FROM scheduled_tasks WHERE id = ?1 AND entity_id = ?2",
+                params![id, entity_id],
+            )
+            .await?;
+        Ok(deleted > 0)
+    }
+}
+
+/// Set how long `conn` waits on a locked database before returning busy.
+pub(crate) async fn set_busy_timeout(conn: &Connection, ms: u64) -> Result<(), StorageError> {
+    // PRAGMA busy_timeout echoes the new value back as a row
+    conn.query(&format!("PRAGMA busy_timeout = {}", ms), ())
+        .await?;
+    Ok(())
+}
+
+/// Decode a `scheduled_tasks` row selected as
+/// `id, entity_id, verb, args, execute_at, priority`.
+fn task_from_row(row: &libsql::Row) -> Result<ScheduledTask, StorageError> {
+    let args_str: String = row.get(3)?;
+    Ok(ScheduledTask {
+        id: row.get(0)?,
+        entity_id: row.get(1)?,
+        verb: row.get(2)?,
+        args: serde_json::from_str(&args_str)?,
+        execute_at: row.get(4)?,
+        priority: row.get(5)?,
+    })
+}
+
+/// Associated data binding a capability's sealed params to its row.
+fn capability_aad(id: &str, cap_type: &str) -> Vec<u8> {
+    // Ids are UUIDs, which never contain NUL, so the split is unambiguous
+    format!("capabilities\0{}\0{}", id, cap_type).into_bytes()
+}
+
+/// Convert a JSON pointer (`/a/b`) to an SQLite JSON path (`$."a"."b"`).
+fn json_pointer_to_path(pointer: &str) -> Result<String, StorageError> {
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(StorageError::InvalidPointer(pointer.to_string()));
+    };
+    let mut path = String::from("$");
+    for segment in rest.split('/') {
+        let key = segment.replace("~1", "/").replace("~0", "~");
+        // SQLite JSON paths have no escape for quotes inside a quoted key
+        if key.contains('"') {
+            return Err(StorageError::InvalidPointer(pointer.to_string()));
+        }
+        path.push_str(&format!(".\"{}\"", key));
+    }
+    Ok(path)
+}
+
+/// A capability to create, without an owner or id yet.
+#[derive(Debug, Clone)]
+pub struct CapabilitySpec {
+    pub cap_type: String,
+    pub params: serde_json::Value,
+}
+
+/// A page of entity ids from a paginated listing.
+#[derive(Debug, Clone)]
+pub struct EntityPage {
+    pub ids: Vec<EntityId>,
+    /// Total number of matching entities across all pages.
+    pub total: u64,
 }
 
 /// A scheduled task.
@@ -655,6 +1595,8 @@ pub struct ScheduledTask {
     pub verb: String,
     pub args: serde_json::Value,
     pub execute_at: i64,
+    /// Higher runs first among due tasks.
+    pub priority: i64,
 }
 
 #[cfg(test)]
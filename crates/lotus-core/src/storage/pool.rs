@@ -0,0 +1,104 @@
+//! Read connection pool for file-backed storage.
+
+use std::ops::Deref;
+use std::sync::Mutex;
+
+use libsql::{Connection, Database};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use super::{StorageError, set_busy_timeout};
+
+/// A fixed-size pool of read connections.
+///
+/// Each connection is checked out exclusively, so with WAL enabled up to
+/// `size` reads run in parallel instead of queueing on one SQLite handle.
+pub(crate) struct ReadPool {
+    idle: Mutex<Vec<Connection>>,
+    permits: Semaphore,
+}
+
+impl ReadPool {
+    /// Open `size` connections to `db`.
+    pub(crate) async fn new(
+        db: &Database,
+        size: usize,
+        busy_timeout_ms: u64,
+    ) -> Result<Self, StorageError> {
+        let mut idle = Vec::with_capacity(size);
+        for _ in 0..size {
+            let conn = db.connect()?;
+            set_busy_timeout(&conn, busy_timeout_ms).await?;
+            idle.push(conn);
+        }
+        Ok(Self {
+            idle: Mutex::new(idle),
+            permits: Semaphore::new(size),
+        })
+    }
+
+    /// Check out a connection, waiting if all are in use.
+    pub(crate) async fn get(&self) -> PooledConnection<'_> {
+        let permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("read pool semaphore is never closed");
+        let conn = self
+            .idle
+            .lock()
+            .expect("read pool mutex poisoned")
+            .pop()
+            .expect("a permit guarantees an idle connection");
+        PooledConnection {
+            pool: self,
+            conn: Some(conn),
+            _permit: permit,
+        }
+    }
+}
+
+/// A connection checked out of a [`ReadPool`], returned on drop.
+pub(crate) struct PooledConnection<'a> {
+    pool: &'a ReadPool,
+    conn: Option<Connection>,
+    // Fields drop after `Drop::drop`, so the connection is back in the idle
+    // list before the permit lets another task in.
+    _permit: SemaphorePermit<'a>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection present until drop")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool
+                .idle
+                .lock()
+                .expect("read pool mutex poisoned")
+                .push(conn);
+        }
+    }
+}
+
+/// A connection to read from: a pooled reader, or the writer connection.
+pub(crate) enum ReadConnection<'a> {
+    Pooled(PooledConnection<'a>),
+    Writer(&'a Connection),
+}
+
+impl Deref for ReadConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        match self {
+            Self::Pooled(conn) => conn,
+            Self::Writer(conn) => conn,
+        }
+    }
+}
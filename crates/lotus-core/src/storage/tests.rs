@@ -1,64 +1,68 @@
 //! Tests for WorldStorage.
 
 use super::*;
-use rhizome_lotus_ir::SExpr;
+use crate::CapabilityError;
 use serde_json::json;
 
-#[test]
-fn test_create_and_get_entity() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_create_and_get_entity() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let id = storage
         .create_entity(json!({"name": "Test Entity"}), None)
+        .await
         .unwrap();
     assert!(id > 0);
 
-    let entity = storage.get_entity(id).unwrap().unwrap();
+    let entity = storage.get_entity(id).await.unwrap().unwrap();
     assert_eq!(entity.id, id);
     assert_eq!(entity.name(), Some("Test Entity"));
     assert!(entity.prototype_id.is_none());
 }
 
-#[test]
-fn test_entity_not_found() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_entity_not_found() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
-    let entity = storage.get_entity(999).unwrap();
+    let entity = storage.get_entity(999).await.unwrap();
     assert!(entity.is_none());
 }
 
-#[test]
-fn test_update_entity() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_update_entity() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let id = storage
         .create_entity(json!({"name": "Original"}), None)
+        .await
         .unwrap();
     storage
         .update_entity(id, json!({"description": "Added description"}))
+        .await
         .unwrap();
 
-    let entity = storage.get_entity(id).unwrap().unwrap();
+    let entity = storage.get_entity(id).await.unwrap().unwrap();
     assert_eq!(entity.name(), Some("Original"));
     assert_eq!(entity.description(), Some("Added description"));
 }
 
-#[test]
-fn test_delete_entity() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_delete_entity() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let id = storage
         .create_entity(json!({"name": "To Delete"}), None)
+        .await
         .unwrap();
-    storage.delete_entity(id).unwrap();
+    storage.delete_entity(id).await.unwrap();
 
-    let entity = storage.get_entity(id).unwrap();
+    let entity = storage.get_entity(id).await.unwrap();
     assert!(entity.is_none());
 }
 
-#[test]
-fn test_prototype_chain() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_prototype_chain() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     // Create a prototype
     let proto_id = storage
@@ -66,6 +70,7 @@ fn test_prototype_chain() {
             json!({"name": "Prototype", "inherited_prop": "from_proto"}),
             None,
         )
+        .await
         .unwrap();
 
     // Create an instance
@@ -74,9 +79,10 @@ fn test_prototype_chain() {
             json!({"name": "Instance", "own_prop": "from_instance"}),
             Some(proto_id),
         )
+        .await
         .unwrap();
 
-    let instance = storage.get_entity(instance_id).unwrap().unwrap();
+    let instance = storage.get_entity(instance_id).await.unwrap().unwrap();
 
     // Should have both own and inherited props
     assert_eq!(instance.name(), Some("Instance")); // Overrides proto
@@ -90,22 +96,25 @@ fn test_prototype_chain() {
     );
 }
 
-#[test]
-fn test_deep_prototype_chain() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_deep_prototype_chain() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     // Create chain: root -> mid -> leaf
     let root_id = storage
         .create_entity(json!({"level": "root", "root_only": true}), None)
+        .await
         .unwrap();
     let mid_id = storage
         .create_entity(json!({"level": "mid", "mid_only": true}), Some(root_id))
+        .await
         .unwrap();
     let leaf_id = storage
         .create_entity(json!({"level": "leaf"}), Some(mid_id))
+        .await
         .unwrap();
 
-    let leaf = storage.get_entity(leaf_id).unwrap().unwrap();
+    let leaf = storage.get_entity(leaf_id).await.unwrap().unwrap();
 
     // Leaf overrides level
     assert_eq!(
@@ -123,111 +132,136 @@ fn test_deep_prototype_chain() {
     );
 }
 
-#[test]
-fn test_add_and_get_verb() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_add_and_get_verb() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let id = storage
         .create_entity(json!({"name": "Test"}), None)
+        .await
         .unwrap();
-    let code = SExpr::call("std.return", vec![SExpr::number(42).erase_type()]);
+    let code = json!(["std.return", 42]);
 
-    storage.add_verb(id, "test_verb", &code).unwrap();
+    storage.add_verb(id, "test_verb", &code).await.unwrap();
 
-    let verb = storage.get_verb(id, "test_verb").unwrap().unwrap();
+    let verb = storage.get_verb(id, "test_verb").await.unwrap().unwrap();
     assert_eq!(verb.name, "test_verb");
     assert_eq!(verb.entity_id, id);
     assert_eq!(verb.code, code);
 }
 
-#[test]
-fn test_verb_not_found() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_verb_not_found() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let id = storage
         .create_entity(json!({"name": "Test"}), None)
+        .await
         .unwrap();
 
-    let verb = storage.get_verb(id, "nonexistent").unwrap();
+    let verb = storage.get_verb(id, "nonexistent").await.unwrap();
     assert!(verb.is_none());
 }
 
-#[test]
-fn test_verb_inheritance() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_verb_inheritance() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let proto_id = storage
         .create_entity(json!({"name": "Proto"}), None)
+        .await
         .unwrap();
     let instance_id = storage
         .create_entity(json!({"name": "Instance"}), Some(proto_id))
+        .await
         .unwrap();
 
-    let proto_code = SExpr::call("std.return", vec![SExpr::string("proto").erase_type()]);
+    let proto_code = json!(["std.return", "proto"]);
     storage
         .add_verb(proto_id, "inherited", &proto_code)
+        .await
         .unwrap();
 
     // Instance should inherit verb from prototype
-    let verb = storage.get_verb(instance_id, "inherited").unwrap().unwrap();
+    let verb = storage
+        .get_verb(instance_id, "inherited")
+        .await
+        .unwrap()
+        .unwrap();
     assert_eq!(verb.entity_id, proto_id);
     assert_eq!(verb.code, proto_code);
 }
 
-#[test]
-fn test_verb_override() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_verb_override() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let proto_id = storage
         .create_entity(json!({"name": "Proto"}), None)
+        .await
         .unwrap();
     let instance_id = storage
         .create_entity(json!({"name": "Instance"}), Some(proto_id))
+        .await
         .unwrap();
 
-    let proto_code = SExpr::call("std.return", vec![SExpr::string("proto").erase_type()]);
-    let instance_code = SExpr::call("std.return", vec![SExpr::string("instance").erase_type()]);
+    let proto_code = json!(["std.return", "proto"]);
+    let instance_code = json!(["std.return", "instance"]);
 
-    storage.add_verb(proto_id, "method", &proto_code).unwrap();
+    storage
+        .add_verb(proto_id, "method", &proto_code)
+        .await
+        .unwrap();
     storage
         .add_verb(instance_id, "method", &instance_code)
+        .await
         .unwrap();
 
     // Instance should use its own version
-    let verb = storage.get_verb(instance_id, "method").unwrap().unwrap();
+    let verb = storage
+        .get_verb(instance_id, "method")
+        .await
+        .unwrap()
+        .unwrap();
     assert_eq!(verb.entity_id, instance_id);
     assert_eq!(verb.code, instance_code);
 
     // Proto should still use proto version
-    let proto_verb = storage.get_verb(proto_id, "method").unwrap().unwrap();
+    let proto_verb = storage.get_verb(proto_id, "method").await.unwrap().unwrap();
     assert_eq!(proto_verb.code, proto_code);
 }
 
-#[test]
-fn test_get_all_verbs() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_get_all_verbs() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let proto_id = storage
         .create_entity(json!({"name": "Proto"}), None)
+        .await
         .unwrap();
     let instance_id = storage
         .create_entity(json!({"name": "Instance"}), Some(proto_id))
+        .await
         .unwrap();
 
     storage
-        .add_verb(proto_id, "proto_only", &SExpr::number(1).erase_type())
+        .add_verb(proto_id, "proto_only", &json!(1))
+        .await
         .unwrap();
     storage
-        .add_verb(proto_id, "overridden", &SExpr::number(2).erase_type())
+        .add_verb(proto_id, "overridden", &json!(2))
+        .await
         .unwrap();
     storage
-        .add_verb(instance_id, "overridden", &SExpr::number(3).erase_type())
+        .add_verb(instance_id, "overridden", &json!(3))
+        .await
         .unwrap();
     storage
-        .add_verb(instance_id, "instance_only", &SExpr::number(4).erase_type())
+        .add_verb(instance_id, "instance_only", &json!(4))
+        .await
         .unwrap();
 
-    let verbs = storage.get_verbs(instance_id).unwrap();
+    let verbs = storage.get_verbs(instance_id).await.unwrap();
     assert_eq!(verbs.len(), 3);
 
     let verb_names: std::collections::HashSet<_> = verbs.iter().map(|v| v.name.as_str()).collect();
@@ -240,64 +274,62 @@ fn test_get_all_verbs() {
     assert_eq!(overridden.entity_id, instance_id);
 }
 
-#[test]
-fn test_update_verb() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_update_verb() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let id = storage
         .create_entity(json!({"name": "Test"}), None)
+        .await
         .unwrap();
-    storage
-        .add_verb(id, "verb", &SExpr::number(1).erase_type())
-        .unwrap();
+    storage.add_verb(id, "verb", &json!(1)).await.unwrap();
 
-    let verb = storage.get_verb(id, "verb").unwrap().unwrap();
-    storage
-        .update_verb(verb.id, &SExpr::number(2).erase_type())
-        .unwrap();
+    let verb = storage.get_verb(id, "verb").await.unwrap().unwrap();
+    storage.update_verb(verb.id, &json!(2)).await.unwrap();
 
-    let updated = storage.get_verb(id, "verb").unwrap().unwrap();
-    assert_eq!(updated.code, SExpr::number(2).erase_type());
+    let updated = storage.get_verb(id, "verb").await.unwrap().unwrap();
+    assert_eq!(updated.code, json!(2));
 }
 
-#[test]
-fn test_delete_verb() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_delete_verb() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let id = storage
         .create_entity(json!({"name": "Test"}), None)
+        .await
         .unwrap();
-    storage
-        .add_verb(id, "verb", &SExpr::number(1).erase_type())
-        .unwrap();
+    storage.add_verb(id, "verb", &json!(1)).await.unwrap();
 
-    let verb = storage.get_verb(id, "verb").unwrap().unwrap();
-    storage.delete_verb(verb.id).unwrap();
+    let verb = storage.get_verb(id, "verb").await.unwrap().unwrap();
+    storage.delete_verb(verb.id).await.unwrap();
 
-    let deleted = storage.get_verb(id, "verb").unwrap();
+    let deleted = storage.get_verb(id, "verb").await.unwrap();
     assert!(deleted.is_none());
 }
 
-#[test]
-fn test_set_prototype() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_set_prototype() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let proto_id = storage
         .create_entity(json!({"inherited": true}), None)
+        .await
         .unwrap();
     let id = storage
         .create_entity(json!({"name": "Test"}), None)
+        .await
         .unwrap();
 
     // Initially no prototype
-    let entity = storage.get_entity(id).unwrap().unwrap();
+    let entity = storage.get_entity(id).await.unwrap().unwrap();
     assert!(entity.prototype_id.is_none());
     assert!(entity.get_prop("inherited").is_none());
 
     // Set prototype
-    storage.set_prototype(id, Some(proto_id)).unwrap();
+    storage.set_prototype(id, Some(proto_id)).await.unwrap();
 
-    let entity = storage.get_entity(id).unwrap().unwrap();
+    let entity = storage.get_entity(id).await.unwrap().unwrap();
     assert_eq!(entity.prototype_id, Some(proto_id));
     assert_eq!(
         entity.get_prop("inherited").and_then(|v| v.as_bool()),
@@ -305,24 +337,21 @@ fn test_set_prototype() {
     );
 }
 
-#[test]
-fn test_delete_entity_cascades_verbs() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_delete_entity_cascades_verbs() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let id = storage
         .create_entity(json!({"name": "Test"}), None)
+        .await
         .unwrap();
-    storage
-        .add_verb(id, "verb1", &SExpr::number(1).erase_type())
-        .unwrap();
-    storage
-        .add_verb(id, "verb2", &SExpr::number(2).erase_type())
-        .unwrap();
+    storage.add_verb(id, "verb1", &json!(1)).await.unwrap();
+    storage.add_verb(id, "verb2", &json!(2)).await.unwrap();
 
-    storage.delete_entity(id).unwrap();
+    storage.delete_entity(id).await.unwrap();
 
     // Entity gone
-    assert!(storage.get_entity(id).unwrap().is_none());
+    assert!(storage.get_entity(id).await.unwrap().is_none());
 
     // Verbs also gone (can't query them by entity anymore since entity doesn't exist)
 }
@@ -331,139 +360,154 @@ fn test_delete_entity_cascades_verbs() {
 // Transaction Tests
 // =========================================================================
 
-#[test]
-fn test_transaction_commit() {
-    let mut storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_transaction_commit() {
+    let mut storage = WorldStorage::in_memory().await.unwrap();
 
-    storage.begin_transaction().unwrap();
+    storage.begin_transaction().await.unwrap();
 
     let id = storage
         .create_entity(json!({"name": "Transaction Test"}), None)
+        .await
         .unwrap();
 
-    storage.commit().unwrap();
+    storage.commit().await.unwrap();
 
     // Entity should exist after commit
-    let entity = storage.get_entity(id).unwrap();
+    let entity = storage.get_entity(id).await.unwrap();
     assert!(entity.is_some());
     assert_eq!(entity.unwrap().name(), Some("Transaction Test"));
 }
 
-#[test]
-fn test_transaction_rollback() {
-    let mut storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_transaction_rollback() {
+    let mut storage = WorldStorage::in_memory().await.unwrap();
 
     // Create entity before transaction
     let before_id = storage
         .create_entity(json!({"name": "Before"}), None)
+        .await
         .unwrap();
 
-    storage.begin_transaction().unwrap();
+    storage.begin_transaction().await.unwrap();
 
     // Create entity in transaction
     let during_id = storage
         .create_entity(json!({"name": "During"}), None)
+        .await
         .unwrap();
 
     // Modify existing entity
     storage
         .update_entity(before_id, json!({"modified": true}))
+        .await
         .unwrap();
 
-    storage.rollback().unwrap();
+    storage.rollback().await.unwrap();
 
     // Entity created during transaction should not exist
-    let during_entity = storage.get_entity(during_id).unwrap();
+    let during_entity = storage.get_entity(during_id).await.unwrap();
     assert!(during_entity.is_none());
 
     // Entity from before should be unmodified
-    let before_entity = storage.get_entity(before_id).unwrap().unwrap();
+    let before_entity = storage.get_entity(before_id).await.unwrap().unwrap();
     assert!(before_entity.get_prop("modified").is_none());
 }
 
-#[test]
-fn test_nested_transaction_commit() {
-    let mut storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_nested_transaction_commit() {
+    let mut storage = WorldStorage::in_memory().await.unwrap();
 
     // Outer transaction
-    let depth0 = storage.begin_transaction().unwrap();
+    let depth0 = storage.begin_transaction().await.unwrap();
     assert_eq!(depth0, 0);
 
     let outer_id = storage
         .create_entity(json!({"name": "Outer"}), None)
+        .await
         .unwrap();
 
     // Inner transaction (savepoint)
-    let depth1 = storage.begin_transaction().unwrap();
+    let depth1 = storage.begin_transaction().await.unwrap();
     assert_eq!(depth1, 1);
 
     let inner_id = storage
         .create_entity(json!({"name": "Inner"}), None)
+        .await
         .unwrap();
 
     // Commit inner
-    storage.commit().unwrap();
+    storage.commit().await.unwrap();
 
     // Commit outer
-    storage.commit().unwrap();
+    storage.commit().await.unwrap();
 
     // Both entities should exist
-    assert!(storage.get_entity(outer_id).unwrap().is_some());
-    assert!(storage.get_entity(inner_id).unwrap().is_some());
+    assert!(storage.get_entity(outer_id).await.unwrap().is_some());
+    assert!(storage.get_entity(inner_id).await.unwrap().is_some());
 }
 
-#[test]
-fn test_nested_transaction_partial_rollback() {
-    let mut storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_nested_transaction_partial_rollback() {
+    let mut storage = WorldStorage::in_memory().await.unwrap();
 
     // Outer transaction
-    storage.begin_transaction().unwrap();
+    storage.begin_transaction().await.unwrap();
 
     let outer_id = storage
         .create_entity(json!({"name": "Outer"}), None)
+        .await
         .unwrap();
 
     // Inner transaction (savepoint)
-    storage.begin_transaction().unwrap();
+    storage.begin_transaction().await.unwrap();
 
     let inner_id = storage
         .create_entity(json!({"name": "Inner"}), None)
+        .await
         .unwrap();
 
     // Rollback inner only
-    storage.rollback().unwrap();
+    storage.rollback().await.unwrap();
 
     // Commit outer
-    storage.commit().unwrap();
+    storage.commit().await.unwrap();
 
     // Outer should exist, inner should not
-    assert!(storage.get_entity(outer_id).unwrap().is_some());
-    assert!(storage.get_entity(inner_id).unwrap().is_none());
+    assert!(storage.get_entity(outer_id).await.unwrap().is_some());
+    assert!(storage.get_entity(inner_id).await.unwrap().is_none());
 }
 
-#[test]
-fn test_transaction_closure() {
-    let mut storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_transaction_closure() {
+    let mut storage = WorldStorage::in_memory().await.unwrap();
 
     // Use transaction closure for automatic commit
-    let result = storage.transaction(|s| {
-        let id = s.create_entity(json!({"name": "Closure Test"}), None)?;
-        Ok(id)
-    });
+    let result = storage
+        .transaction(async |s| {
+            let id = s
+                .create_entity(json!({"name": "Closure Test"}), None)
+                .await?;
+            Ok(id)
+        })
+        .await;
 
     let id = result.unwrap();
-    assert!(storage.get_entity(id).unwrap().is_some());
+    assert!(storage.get_entity(id).await.unwrap().is_some());
 }
 
-#[test]
-fn test_transaction_closure_rollback_on_error() {
-    let mut storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_transaction_closure_rollback_on_error() {
+    let mut storage = WorldStorage::in_memory().await.unwrap();
 
     // Use transaction closure that fails
-    let result: Result<(), StorageError> = storage.transaction(|s| {
-        s.create_entity(json!({"name": "Will Rollback"}), None)?;
-        Err(StorageError::Transaction("intentional error".to_string()))
-    });
+    let result: Result<(), StorageError> = storage
+        .transaction(async |s| {
+            s.create_entity(json!({"name": "Will Rollback"}), None)
+                .await?;
+            Err(StorageError::Transaction("intentional error".to_string()))
+        })
+        .await;
 
     assert!(result.is_err());
 
@@ -472,38 +516,38 @@ fn test_transaction_closure_rollback_on_error() {
     // test above confirms the mechanism works
 }
 
-#[test]
-fn test_in_transaction_flag() {
-    let mut storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_in_transaction_flag() {
+    let mut storage = WorldStorage::in_memory().await.unwrap();
 
     assert!(!storage.in_transaction());
 
-    storage.begin_transaction().unwrap();
+    storage.begin_transaction().await.unwrap();
     assert!(storage.in_transaction());
 
-    storage.begin_transaction().unwrap(); // nested
+    storage.begin_transaction().await.unwrap(); // nested
     assert!(storage.in_transaction());
 
-    storage.commit().unwrap(); // inner
+    storage.commit().await.unwrap(); // inner
     assert!(storage.in_transaction());
 
-    storage.commit().unwrap(); // outer
+    storage.commit().await.unwrap(); // outer
     assert!(!storage.in_transaction());
 }
 
-#[test]
-fn test_commit_without_transaction_fails() {
-    let mut storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_commit_without_transaction_fails() {
+    let mut storage = WorldStorage::in_memory().await.unwrap();
 
-    let result = storage.commit();
+    let result = storage.commit().await;
     assert!(result.is_err());
 }
 
-#[test]
-fn test_rollback_without_transaction_fails() {
-    let mut storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_rollback_without_transaction_fails() {
+    let mut storage = WorldStorage::in_memory().await.unwrap();
 
-    let result = storage.rollback();
+    let result = storage.rollback().await;
     assert!(result.is_err());
 }
 
@@ -511,56 +555,65 @@ fn test_rollback_without_transaction_fails() {
 // Capability-Gated Verb Tests
 // =========================================================================
 
-#[test]
-fn test_add_verb_with_capability_requirement() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_add_verb_with_capability_requirement() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let id = storage
         .create_entity(json!({"name": "Test Entity"}), None)
+        .await
         .unwrap();
 
     // Add verb with required capability
-    let code = SExpr::call("std.return", vec![SExpr::number(42).erase_type()]);
+    let code = json!(["std.return", 42]);
     storage
         .add_verb_with_cap(id, "protected_verb", &code, Some("admin.execute"))
+        .await
         .unwrap();
 
-    let verb = storage.get_verb(id, "protected_verb").unwrap().unwrap();
+    let verb = storage
+        .get_verb(id, "protected_verb")
+        .await
+        .unwrap()
+        .unwrap();
     assert_eq!(verb.required_capability, Some("admin.execute".to_string()));
 }
 
-#[test]
-fn test_add_verb_without_capability_requirement() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_add_verb_without_capability_requirement() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let id = storage
         .create_entity(json!({"name": "Test Entity"}), None)
+        .await
         .unwrap();
 
     // Add verb without capability requirement
-    let code = SExpr::call("std.return", vec![SExpr::number(42).erase_type()]);
-    storage.add_verb(id, "public_verb", &code).unwrap();
+    let code = json!(["std.return", 42]);
+    storage.add_verb(id, "public_verb", &code).await.unwrap();
 
-    let verb = storage.get_verb(id, "public_verb").unwrap().unwrap();
+    let verb = storage.get_verb(id, "public_verb").await.unwrap().unwrap();
     assert!(verb.required_capability.is_none());
 }
 
-#[test]
-fn test_get_verbs_includes_capability_requirement() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_get_verbs_includes_capability_requirement() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let id = storage
         .create_entity(json!({"name": "Test Entity"}), None)
+        .await
         .unwrap();
 
     // Add verbs with and without capability requirements
-    let code = SExpr::number(1).erase_type();
-    storage.add_verb(id, "public", &code).unwrap();
+    let code = json!(1);
+    storage.add_verb(id, "public", &code).await.unwrap();
     storage
         .add_verb_with_cap(id, "protected", &code, Some("admin.execute"))
+        .await
         .unwrap();
 
-    let verbs = storage.get_verbs(id).unwrap();
+    let verbs = storage.get_verbs(id).await.unwrap();
     assert_eq!(verbs.len(), 2);
 
     let public_verb = verbs.iter().find(|v| v.name == "public").unwrap();
@@ -573,19 +626,21 @@ fn test_get_verbs_includes_capability_requirement() {
     );
 }
 
-#[test]
-fn test_inherited_verb_capability_requirement() {
-    let storage = WorldStorage::in_memory().unwrap();
+#[tokio::test]
+async fn test_inherited_verb_capability_requirement() {
+    let storage = WorldStorage::in_memory().await.unwrap();
 
     let proto_id = storage
         .create_entity(json!({"name": "Proto"}), None)
+        .await
         .unwrap();
     let instance_id = storage
         .create_entity(json!({"name": "Instance"}), Some(proto_id))
+        .await
         .unwrap();
 
     // Add protected verb to prototype
-    let code = SExpr::number(1).erase_type();
+    let code = json!(1);
     storage
         .add_verb_with_cap(
             proto_id,
@@ -593,13 +648,820 @@ fn test_inherited_verb_capability_requirement() {
             &code,
             Some("entity.control"),
         )
+        .await
         .unwrap();
 
     // Instance should inherit the verb with its capability requirement
     let verb = storage
         .get_verb(instance_id, "inherited_protected")
+        .await
         .unwrap()
         .unwrap();
     assert_eq!(verb.entity_id, proto_id);
     assert_eq!(verb.required_capability, Some("entity.control".to_string()));
 }
+
+// =========================================================================
+// Prototype Listing Tests
+// =========================================================================
+
+#[tokio::test]
+async fn test_entities_by_prototype_pagination() {
+    let storage = WorldStorage::in_memory().await.unwrap();
+
+    let proto_id = storage
+        .create_entity(json!({"name": "Notebook"}), None)
+        .await
+        .unwrap();
+    let other_proto_id = storage
+        .create_entity(json!({"name": "File"}), None)
+        .await
+        .unwrap();
+
+    let mut notebook_ids = Vec::new();
+    for i in 0..5 {
+        let id = storage
+            .create_entity(json!({"name": format!("Notebook {}", i)}), Some(proto_id))
+            .await
+            .unwrap();
+        notebook_ids.push(id);
+    }
+    storage
+        .create_entity(json!({"name": "Unrelated"}), Some(other_proto_id))
+        .await
+        .unwrap();
+
+    let first = storage.entities_by_prototype(proto_id, 0, 2).await.unwrap();
+    assert_eq!(first.total, 5);
+    assert_eq!(first.ids, notebook_ids[0..2]);
+
+    let second = storage.entities_by_prototype(proto_id, 2, 2).await.unwrap();
+    assert_eq!(second.ids, notebook_ids[2..4]);
+
+    let last = storage.entities_by_prototype(proto_id, 4, 2).await.unwrap();
+    assert_eq!(last.ids, notebook_ids[4..]);
+
+    let past_end = storage
+        .entities_by_prototype(proto_id, 10, 2)
+        .await
+        .unwrap();
+    assert!(past_end.ids.is_empty());
+    assert_eq!(past_end.total, 5);
+
+    // Huge values saturate instead of wrapping negative
+    let everything = storage
+        .entities_by_prototype(proto_id, 0, u64::MAX)
+        .await
+        .unwrap();
+    assert_eq!(everything.ids, notebook_ids);
+    let nothing = storage
+        .entities_by_prototype(proto_id, u64::MAX, 2)
+        .await
+        .unwrap();
+    assert!(nothing.ids.is_empty());
+}
+
+// =========================================================================
+// Encryption Tests
+// =========================================================================
+
+#[tokio::test]
+async fn test_encrypted_capability_params() {
+    let path = std::env::temp_dir().join(format!("lotus-enc-{}.db", uuid::Uuid::new_v4()));
+    let path_str = path.to_str().unwrap();
+    let key = [7u8; 32];
+
+    let cap_id = {
+        let storage = WorldStorage::open_encrypted(path_str, &key).await.unwrap();
+        let owner = storage
+            .create_entity(json!({"name": "Owner"}), None)
+            .await
+            .unwrap();
+        let cap_id = storage
+            .create_capability(owner, "ai.generate", json!({"api_key": "sk-super-secret"}))
+            .await
+            .unwrap();
+
+        // Round-trips through the API
+        let cap = storage.get_capability(&cap_id).await.unwrap().unwrap();
+        assert_eq!(cap.params, json!({"api_key": "sk-super-secret"}));
+        let caps = storage.get_capabilities(owner).await.unwrap();
+        assert_eq!(caps[0].params, json!({"api_key": "sk-super-secret"}));
+        cap_id
+    };
+
+    // Secret never hits the file in plaintext, but non-sensitive data does
+    let raw = std::fs::read(&path).unwrap();
+    let contains = |needle: &str| raw.windows(needle.len()).any(|w| w == needle.as_bytes());
+    assert!(!contains("sk-super-secret"));
+    assert!(contains("ai.generate"));
+
+    // Reopening with the right key decrypts, a wrong key or no key fails
+    let storage = WorldStorage::open_encrypted(path_str, &key).await.unwrap();
+    let cap = storage.get_capability(&cap_id).await.unwrap().unwrap();
+    assert_eq!(cap.params["api_key"], "sk-super-secret");
+
+    let wrong = WorldStorage::open_encrypted(path_str, &[8u8; 32])
+        .await
+        .unwrap();
+    assert!(matches!(
+        wrong.get_capability(&cap_id).await,
+        Err(StorageError::Encryption(_))
+    ));
+
+    let plain = WorldStorage::open(path_str).await.unwrap();
+    assert!(matches!(
+        plain.get_capability(&cap_id).await,
+        Err(StorageError::Encryption(_))
+    ));
+
+    drop((storage, wrong, plain));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_encrypted_params_bound_to_row() {
+    let storage = WorldStorage::open_encrypted(":memory:", &[7u8; 32])
+        .await
+        .unwrap();
+    let victim = storage.create_entity(json!({}), None).await.unwrap();
+    let attacker = storage.create_entity(json!({}), None).await.unwrap();
+
+    let secret = storage
+        .create_capability(victim, "ai.generate", json!({"api_key": "sk-victim"}))
+        .await
+        .unwrap();
+    let own = storage
+        .create_capability(attacker, "ai.generate", json!({"api_key": "sk-own"}))
+        .await
+        .unwrap();
+
+    // Copy the victim's sealed params onto the attacker's row
+    storage
+        .conn
+        .execute(
+            "UPDATE capabilities SET params = (SELECT params FROM capabilities WHERE id = ?1)
+             WHERE id = ?2",
+            params![secret.clone(), own.clone()],
+        )
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        storage.get_capability(&own).await,
+        Err(StorageError::Encryption(_))
+    ));
+    let cap = storage.get_capability(&secret).await.unwrap().unwrap();
+    assert_eq!(cap.params["api_key"], "sk-victim");
+}
+
+// =========================================================================
+// Prop Patch Tests
+// =========================================================================
+
+#[tokio::test]
+async fn test_patch_entity_nested_key() {
+    let storage = WorldStorage::in_memory().await.unwrap();
+
+    let id = storage
+        .create_entity(json!({"name": "Hero", "stats": {"hp": 10, "mp": 5}}), None)
+        .await
+        .unwrap();
+
+    storage
+        .patch_entity(id, "/stats/hp", &json!(7))
+        .await
+        .unwrap();
+    // Missing intermediate objects are created; values keep their JSON type
+    storage
+        .patch_entity(id, "/inventory/slots", &json!([1, 2]))
+        .await
+        .unwrap();
+    // Escaped segments address keys containing '/'
+    storage
+        .patch_entity(id, "/a~1b", &json!("slash"))
+        .await
+        .unwrap();
+
+    let entity = storage.get_entity_raw(id).await.unwrap().unwrap();
+    assert_eq!(
+        entity.props,
+        json!({
+            "name": "Hero",
+            "stats": {"hp": 7, "mp": 5},
+            "inventory": {"slots": [1, 2]},
+            "a/b": "slash",
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_remove_entity_prop_keeps_siblings() {
+    let storage = WorldStorage::in_memory().await.unwrap();
+
+    let id = storage
+        .create_entity(json!({"name": "Hero", "stats": {"hp": 10, "mp": 5}}), None)
+        .await
+        .unwrap();
+
+    storage.remove_entity_prop(id, "/stats/mp").await.unwrap();
+    storage.remove_entity_prop(id, "/missing").await.unwrap();
+
+    let entity = storage.get_entity_raw(id).await.unwrap().unwrap();
+    assert_eq!(entity.props, json!({"name": "Hero", "stats": {"hp": 10}}));
+}
+
+#[tokio::test]
+async fn test_patch_entity_errors() {
+    let storage = WorldStorage::in_memory().await.unwrap();
+
+    let id = storage
+        .create_entity(json!({"name": "Hero", "tags": [1, 2]}), None)
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        storage.patch_entity(id, "name", &json!("x")).await,
+        Err(StorageError::InvalidPointer(_))
+    ));
+    // Arrays and scalars can't be descended into; the write must not be
+    // reported as done
+    for pointer in ["/tags/0", "/name/first"] {
+        assert!(matches!(
+            storage.patch_entity(id, pointer, &json!(9)).await,
+            Err(StorageError::InvalidPointer(_))
+        ));
+        assert!(matches!(
+            storage.remove_entity_prop(id, pointer).await,
+            Err(StorageError::InvalidPointer(_))
+        ));
+    }
+    let entity = storage.get_entity_raw(id).await.unwrap().unwrap();
+    assert_eq!(entity.props, json!({"name": "Hero", "tags": [1, 2]}));
+    // Setting JSON null is a real write, not a failure
+    storage
+        .patch_entity(id, "/nothing", &json!(null))
+        .await
+        .unwrap();
+    assert!(matches!(
+        storage.patch_entity(999, "/name", &json!("x")).await,
+        Err(StorageError::EntityNotFound(999))
+    ));
+    assert!(matches!(
+        storage.remove_entity_prop(999, "/name").await,
+        Err(StorageError::EntityNotFound(999))
+    ));
+}
+
+// =========================================================================
+// Read Pool Tests
+// =========================================================================
+
+#[tokio::test]
+async fn test_read_pool_concurrent_reads() {
+    let path = std::env::temp_dir().join(format!("lotus-pool-{}.db", uuid::Uuid::new_v4()));
+    let options = StorageOptions {
+        read_pool_size: 2,
+        ..Default::default()
+    };
+    let mut storage = WorldStorage::open_with(path.to_str().unwrap(), &options)
+        .await
+        .unwrap();
+
+    let a = storage
+        .create_entity(json!({"name": "A"}), None)
+        .await
+        .unwrap();
+    let b = storage
+        .create_entity(json!({"name": "B"}), None)
+        .await
+        .unwrap();
+
+    {
+        // Two readers can be held at once without waiting on each other...
+        let timeout = std::time::Duration::from_millis(200);
+        let first = tokio::time::timeout(timeout, storage.reader()).await;
+        let second = tokio::time::timeout(timeout, storage.reader()).await;
+        assert!(first.is_ok() && second.is_ok());
+        // ...and the pool is bounded
+        assert!(
+            tokio::time::timeout(timeout, storage.reader())
+                .await
+                .is_err()
+        );
+    }
+
+    let (entity_a, entity_b) = tokio::join!(storage.get_entity(a), storage.get_entity(b));
+    assert_eq!(entity_a.unwrap().unwrap().name(), Some("A"));
+    assert_eq!(entity_b.unwrap().unwrap().name(), Some("B"));
+
+    // Inside a transaction, reads see uncommitted writes; pooled readers don't
+    storage.begin_transaction().await.unwrap();
+    storage
+        .update_entity(a, json!({"name": "A2"}))
+        .await
+        .unwrap();
+    let entity = storage.get_entity(a).await.unwrap().unwrap();
+    assert_eq!(entity.name(), Some("A2"));
+    {
+        let pool = storage.read_pool.as_ref().unwrap();
+        let reader = pool.get().await;
+        let mut rows = reader
+            .query("SELECT props FROM entities WHERE id = ?1", params![a])
+            .await
+            .unwrap();
+        let props: String = rows.next().await.unwrap().unwrap().get(0).unwrap();
+        assert_eq!(props, r#"{"name":"A"}"#);
+    }
+    storage.commit().await.unwrap();
+
+    let entity = storage.get_entity(a).await.unwrap().unwrap();
+    assert_eq!(entity.name(), Some("A2"));
+
+    drop(storage);
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
+
+#[tokio::test]
+async fn test_read_pool_skipped_for_unshareable_databases() {
+    let options = StorageOptions {
+        read_pool_size: 2,
+        ..Default::default()
+    };
+    for path in [":memory:", "", "file::memory:", "file:pool?mode=memory"] {
+        let storage = WorldStorage::open_with(path, &options).await.unwrap();
+        assert!(storage.read_pool.is_none(), "pool enabled for {:?}", path);
+
+        // Reads go through the writer and see its schema and data
+        let id = storage
+            .create_entity(json!({"name": "A"}), None)
+            .await
+            .unwrap();
+        let entity = storage.get_entity(id).await.unwrap().unwrap();
+        assert_eq!(entity.name(), Some("A"), "read failed for {:?}", path);
+    }
+}
+
+// =========================================================================
+// Bulk Capability Grant Tests
+// =========================================================================
+
+#[tokio::test]
+async fn test_grant_capabilities() {
+    let mut storage = WorldStorage::in_memory().await.unwrap();
+
+    let user = storage
+        .create_entity(json!({"name": "New User"}), None)
+        .await
+        .unwrap();
+
+    let specs = [
+        CapabilitySpec {
+            cap_type: "entity.control".to_string(),
+            params: json!({"target_id": user}),
+        },
+        CapabilitySpec {
+            cap_type: "fs.read".to_string(),
+            params: json!({"path": "/home/user"}),
+        },
+        CapabilitySpec {
+            cap_type: "net.request".to_string(),
+            params: json!({"domain": "example.com"}),
+        },
+    ];
+    let ids = storage.grant_capabilities(user, &specs).await.unwrap();
+    assert_eq!(ids.len(), 3);
+    assert!(!storage.in_transaction());
+
+    let caps = storage.get_capabilities(user).await.unwrap();
+    assert_eq!(caps.len(), 3);
+    for (id, spec) in ids.iter().zip(&specs) {
+        let cap = caps.iter().find(|c| &c.id == id).unwrap();
+        assert_eq!(cap.owner_id, user);
+        assert_eq!(cap.cap_type, spec.cap_type);
+        assert_eq!(cap.params, spec.params);
+    }
+}
+
+// =========================================================================
+// Busy Handling & Checkpoint Tests
+// =========================================================================
+
+#[tokio::test]
+async fn test_busy_timeout_applied() {
+    let options = StorageOptions {
+        busy_timeout_ms: 1234,
+        ..Default::default()
+    };
+    let storage = WorldStorage::open_with(":memory:", &options).await.unwrap();
+
+    let mut rows = storage.conn.query("PRAGMA busy_timeout", ()).await.unwrap();
+    let timeout: i64 = rows.next().await.unwrap().unwrap().get(0).unwrap();
+    assert_eq!(timeout, 1234);
+}
+
+#[tokio::test]
+async fn test_checkpoint_truncates_wal() {
+    let path = std::env::temp_dir().join(format!("lotus-wal-{}.db", uuid::Uuid::new_v4()));
+    let wal_path = format!("{}-wal", path.display());
+    let options = StorageOptions {
+        read_pool_size: 1,
+        ..Default::default()
+    };
+    let storage = WorldStorage::open_with(path.to_str().unwrap(), &options)
+        .await
+        .unwrap();
+
+    for i in 0..200 {
+        storage
+            .create_entity(
+                json!({"name": format!("Entity {}", i), "padding": "x".repeat(512)}),
+                None,
+            )
+            .await
+            .unwrap();
+    }
+    let before = std::fs::metadata(&wal_path).unwrap().len();
+    assert!(before > 0);
+
+    let checkpoint = storage.checkpoint().await.unwrap();
+    assert!(!checkpoint.busy);
+    assert_eq!(checkpoint.wal_frames, 0);
+    let after = std::fs::metadata(&wal_path).unwrap().len();
+    assert!(after < before, "WAL should shrink: {} -> {}", before, after);
+
+    // Data survives the checkpoint
+    assert!(storage.get_entity(200).await.unwrap().is_some());
+
+    drop(storage);
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
+
+// =========================================================================
+// Reference Integrity Tests
+// =========================================================================
+
+#[tokio::test]
+async fn test_references_and_backlinks() {
+    let storage = WorldStorage::in_memory().await.unwrap();
+
+    let note = storage
+        .create_entity(json!({"name": "Note"}), None)
+        .await
+        .unwrap();
+    let other = storage
+        .create_entity(json!({"name": "Other"}), None)
+        .await
+        .unwrap();
+
+    storage.add_reference(note, other, "link").await.unwrap();
+    storage.add_reference(note, other, "link").await.unwrap(); // idempotent
+
+    let expected = vec![EntityReference {
+        source_id: note,
+        target_id: other,
+        kind: "link".to_string(),
+    }];
+    assert_eq!(storage.get_references(note).await.unwrap(), expected);
+    assert_eq!(storage.get_backlinks(other).await.unwrap(), expected);
+
+    storage.remove_reference(note, other, "link").await.unwrap();
+    assert!(storage.get_backlinks(other).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_gc_dangling_references() {
+    let mut storage = WorldStorage::in_memory().await.unwrap();
+
+    let note = storage
+        .create_entity(json!({"name": "Note"}), None)
+        .await
+        .unwrap();
+    let kept = storage
+        .create_entity(json!({"name": "Kept"}), None)
+        .await
+        .unwrap();
+    let deleted = storage
+        .create_entity(json!({"name": "Deleted"}), None)
+        .await
+        .unwrap();
+
+    storage.add_reference(note, kept, "link").await.unwrap();
+    storage.add_reference(note, deleted, "link").await.unwrap();
+    storage.delete_entity(deleted).await.unwrap();
+
+    let dangling = vec![EntityReference {
+        source_id: note,
+        target_id: deleted,
+        kind: "link".to_string(),
+    }];
+
+    // Validation reports without modifying
+    assert_eq!(storage.validate_integrity().await.unwrap(), dangling);
+    assert_eq!(storage.get_references(note).await.unwrap().len(), 2);
+
+    // GC removes only the dangling edge
+    assert_eq!(storage.gc_dangling_references().await.unwrap(), dangling);
+    let remaining = storage.get_references(note).await.unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].target_id, kept);
+    assert!(storage.validate_integrity().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_delete_entity_removes_outgoing_references() {
+    let storage = WorldStorage::in_memory().await.unwrap();
+
+    let note = storage
+        .create_entity(json!({"name": "Note"}), None)
+        .await
+        .unwrap();
+    let other = storage
+        .create_entity(json!({"name": "Other"}), None)
+        .await
+        .unwrap();
+
+    storage.add_reference(note, other, "link").await.unwrap();
+    storage.delete_entity(note).await.unwrap();
+
+    assert!(storage.get_backlinks(other).await.unwrap().is_empty());
+}
+
+// =========================================================================
+// Storage Accounting Tests
+// =========================================================================
+
+#[tokio::test]
+async fn test_entity_storage_bytes() {
+    let storage = WorldStorage::in_memory().await.unwrap();
+
+    let id = storage
+        .create_entity(json!({"name": "Tenant"}), None)
+        .await
+        .unwrap();
+    let other = storage
+        .create_entity(json!({"name": "Other"}), None)
+        .await
+        .unwrap();
+
+    let before = storage.entity_storage_bytes(id).await.unwrap();
+    assert_eq!(before, r#"{"name":"Tenant"}"#.len() as u64);
+    let total_before = storage.total_storage_bytes().await.unwrap();
+
+    // A large value grows usage by roughly its size; multi-byte chars count as bytes
+    let blob = "é".repeat(50_000);
+    storage
+        .update_entity(id, json!({"data": blob}))
+        .await
+        .unwrap();
+    let after = storage.entity_storage_bytes(id).await.unwrap();
+    let growth = after - before;
+    assert!((100_000..100_100).contains(&growth), "grew by {}", growth);
+
+    // Owned verbs and capabilities count too
+    storage
+        .add_verb(id, "look", &json!(["std.return", 1]))
+        .await
+        .unwrap();
+    storage
+        .create_capability(id, "fs.read", json!({"path": "/data"}))
+        .await
+        .unwrap();
+    assert!(storage.entity_storage_bytes(id).await.unwrap() > after);
+
+    // Other entities are unaffected; the world total includes everything
+    assert_eq!(
+        storage.entity_storage_bytes(other).await.unwrap(),
+        r#"{"name":"Other"}"#.len() as u64
+    );
+    let total_after = storage.total_storage_bytes().await.unwrap();
+    assert_eq!(
+        total_after - total_before,
+        storage.entity_storage_bytes(id).await.unwrap() - before
+    );
+
+    assert!(matches!(
+        storage.entity_storage_bytes(999).await,
+        Err(StorageError::EntityNotFound(999))
+    ));
+}
+
+// =========================================================================
+// Capability Delegation Tests
+// =========================================================================
+
+#[tokio::test]
+async fn test_delegate_and_revoke_capability() {
+    let storage = WorldStorage::in_memory().await.unwrap();
+    let alice = storage.create_entity(json!({}), None).await.unwrap();
+    let bob = storage.create_entity(json!({}), None).await.unwrap();
+    let carol = storage.create_entity(json!({}), None).await.unwrap();
+
+    let root = storage
+        .create_capability(
+            alice,
+            "fs.write",
+            json!({"path": "/data", "ops": ["read", "write", "delegate"]}),
+        )
+        .await
+        .unwrap();
+    let to_bob = storage
+        .delegate_capability(&root, alice, bob, &json!({"ops": ["read", "delegate"]}), 0)
+        .await
+        .unwrap();
+    let to_carol = storage
+        .delegate_capability(
+            &to_bob,
+            bob,
+            carol,
+            &json!({"ops": ["read"], "expires_at": 1_000}),
+            0,
+        )
+        .await
+        .unwrap();
+
+    let cap = storage.get_capability(&to_carol).await.unwrap().unwrap();
+    assert_eq!(cap.owner_id, carol);
+    assert_eq!(cap.cap_type, "fs.write");
+    assert_eq!(cap.parent_id.as_deref(), Some(to_bob.as_str()));
+    assert_eq!(
+        cap.params,
+        json!({"path": "/data", "ops": ["read"], "expires_at": 1_000})
+    );
+
+    // Bob can't hand out more than he has
+    assert!(matches!(
+        storage
+            .delegate_capability(&to_bob, bob, carol, &json!({"ops": ["write"]}), 0)
+            .await,
+        Err(StorageError::Capability(
+            CapabilityError::NotNarrower { .. }
+        ))
+    ));
+    // Carol's copy dropped "delegate", so she can't pass it on
+    assert!(matches!(
+        storage
+            .delegate_capability(&to_carol, carol, alice, &json!({}), 0)
+            .await,
+        Err(StorageError::Capability(
+            CapabilityError::OperationNotAllowed { .. }
+        ))
+    ));
+    assert!(matches!(
+        storage
+            .delegate_capability("missing", alice, carol, &json!({}), 0)
+            .await,
+        Err(StorageError::CapabilityNotFound(_))
+    ));
+
+    // Revoking Bob's copy takes Carol's with it but leaves the root
+    assert_eq!(storage.revoke_capability(&to_bob).await.unwrap(), 2);
+    assert!(storage.get_capability(&to_carol).await.unwrap().is_none());
+    assert!(storage.get_capability(&root).await.unwrap().is_some());
+    assert_eq!(storage.revoke_capability(&to_bob).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_delegate_capability_requires_live_owned_parent() {
+    let storage = WorldStorage::in_memory().await.unwrap();
+    let alice = storage.create_entity(json!({}), None).await.unwrap();
+    let mallory = storage.create_entity(json!({}), None).await.unwrap();
+
+    let root = storage
+        .create_capability(
+            alice,
+            "fs.read",
+            json!({"path": "/data", "expires_at": 5_000}),
+        )
+        .await
+        .unwrap();
+
+    // Knowing the id isn't enough; only the owner can delegate
+    let result = storage
+        .delegate_capability(&root, mallory, mallory, &json!({}), 0)
+        .await;
+    assert!(matches!(
+        result,
+        Err(StorageError::NotCapabilityOwner { entity_id, .. }) if entity_id == mallory
+    ));
+    assert!(storage.get_capabilities(mallory).await.unwrap().is_empty());
+
+    // An expired capability can't be handed on
+    assert!(matches!(
+        storage
+            .delegate_capability(&root, alice, mallory, &json!({}), 5_000)
+            .await,
+        Err(StorageError::Capability(CapabilityError::Expired { .. }))
+    ));
+    storage
+        .delegate_capability(&root, alice, mallory, &json!({}), 4_999)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_delete_entity_revokes_delegated_capabilities() {
+    let storage = WorldStorage::in_memory().await.unwrap();
+    let alice = storage.create_entity(json!({}), None).await.unwrap();
+    let bob = storage.create_entity(json!({}), None).await.unwrap();
+
+    let root = storage
+        .create_capability(alice, "net.request", json!({"url": "https://example.com"}))
+        .await
+        .unwrap();
+    let delegated = storage
+        .delegate_capability(&root, alice, bob, &json!({}), 0)
+        .await
+        .unwrap();
+    let own = storage
+        .create_capability(bob, "fs.read", json!({"path": "/bob"}))
+        .await
+        .unwrap();
+
+    storage.delete_entity(alice).await.unwrap();
+    assert!(storage.get_capability(&delegated).await.unwrap().is_none());
+    assert!(storage.get_capability(&own).await.unwrap().is_some());
+}
+
+// =========================================================================
+// Event Subscription Tests
+// =========================================================================
+
+#[tokio::test]
+async fn test_event_subscriptions() {
+    let storage = WorldStorage::in_memory().await.unwrap();
+    let source = storage.create_entity(json!({}), None).await.unwrap();
+    let handler = storage.create_entity(json!({}), None).await.unwrap();
+
+    let id = storage
+        .subscribe(source, "changed", handler, "on_change")
+        .await
+        .unwrap();
+    // Subscribing again keeps the existing subscription
+    assert_eq!(
+        storage
+            .subscribe(source, "changed", handler, "on_change")
+            .await
+            .unwrap(),
+        id
+    );
+    storage
+        .subscribe(source, "changed", handler, "audit")
+        .await
+        .unwrap();
+
+    let subs = storage.get_subscribers(source, "changed").await.unwrap();
+    assert_eq!(subs.len(), 2);
+    assert_eq!(
+        subs[0],
+        EventSubscription {
+            id,
+            source_id: source,
+            event: "changed".to_string(),
+            handler_id: handler,
+            handler_verb: "on_change".to_string(),
+        }
+    );
+    assert!(
+        storage
+            .get_subscribers(source, "deleted")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    assert!(
+        storage
+            .unsubscribe(source, "changed", handler, "audit")
+            .await
+            .unwrap()
+    );
+    assert!(
+        !storage
+            .unsubscribe(source, "changed", handler, "audit")
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        storage
+            .get_subscribers(source, "changed")
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // Deleting either side drops the subscription
+    storage.delete_entity(handler).await.unwrap();
+    assert!(
+        storage
+            .get_subscribers(source, "changed")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}